@@ -0,0 +1,70 @@
+//! Compares the tile renderer against locking a shared framebuffer for every pixel, which is how
+//! `main` used to work. Run with `cargo bench --bench tiles`.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use raytrace::{
+    center_rel,
+    render::{TileOrder, TileRenderer},
+    Framebuffer, Pos3, Scene, Viewport,
+};
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 256;
+const RUNS: u32 = 3;
+
+fn time(mut f: impl FnMut() -> Framebuffer) -> Duration {
+    // warm up caches and the thread pool
+    f();
+
+    let now = Instant::now();
+    for _ in 0..RUNS {
+        std::hint::black_box(f());
+    }
+    now.elapsed() / RUNS
+}
+
+fn main() {
+    let scene = Scene::default();
+    let viewport = Viewport::new(Pos3::new(0.0, 0.0, -5.0), 1.0, 1.0);
+    let shade = |x: usize, y: usize| {
+        let (cx, cy) = center_rel(WIDTH, HEIGHT, x, y);
+        scene.trace_ray(
+            viewport.ray_from_coord(cx, cy, WIDTH, HEIGHT),
+            1.0,
+            f32::MAX,
+            3,
+        )
+    };
+
+    println!("{WIDTH}x{HEIGHT}, average of {RUNS} runs");
+
+    let mutex = time(|| {
+        let image = Mutex::new(Framebuffer::new(WIDTH, HEIGHT));
+        (0..WIDTH).into_par_iter().for_each(|x| {
+            for y in 0..HEIGHT {
+                let color = shade(x, y);
+                image.lock().unwrap().set(x, y, color);
+            }
+        });
+        image.into_inner().unwrap()
+    });
+    println!("{:<16} {mutex:?}", "mutex per pixel");
+
+    for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+        for tile_size in [16, 32, 64] {
+            let renderer = TileRenderer::new(tile_size, order);
+            let tiled = time(|| renderer.render(WIDTH, HEIGHT, shade));
+
+            println!(
+                "{:<16} {tiled:?} ({:.2}x)",
+                format!("{order:?} {tile_size}px"),
+                mutex.as_secs_f64() / tiled.as_secs_f64()
+            );
+        }
+    }
+}
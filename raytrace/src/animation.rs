@@ -0,0 +1,413 @@
+use std::sync::Arc;
+
+use crate::{objects::Sphere, Color, Mat4, Pos3, Quat, Scene, Vec3, Viewport};
+
+/// How a `Track` fills in the time between two keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Straight from one key to the next
+    #[default]
+    Linear,
+    /// A Catmull-Rom spline through every key, so motion doesn't jolt as it passes one
+    Cubic,
+}
+
+impl Interpolation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Linear => "linear",
+            Interpolation::Cubic => "cubic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Interpolation> {
+        [Interpolation::Linear, Interpolation::Cubic]
+            .into_iter()
+            .find(|i| i.name() == name)
+    }
+}
+
+/// Values that can be blended between keyframes
+pub trait Interpolate: Copy {
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+
+    /// The point `t` of the way from `p1` to `p2` on a Catmull-Rom spline through all four
+    fn cubic(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + ((b - a) * t)
+    }
+
+    fn cubic(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+        let (t2, t3) = (t * t, t * t * t);
+        0.5 * ((2.0 * p1)
+            + ((p2 - p0) * t)
+            + (((2.0 * p0) - (5.0 * p1) + (4.0 * p2) - p3) * t2)
+            + (((3.0 * p1) - p0 - (3.0 * p2) + p3) * t3))
+    }
+}
+
+/// Implements `Interpolate` one component at a time
+macro_rules! interpolate_components {
+    ($ty:ty, $new:expr, $($field:tt),+) => {
+        impl Interpolate for $ty {
+            fn lerp(a: Self, b: Self, t: f32) -> Self {
+                $new($(f32::lerp(a.$field, b.$field, t)),+)
+            }
+
+            fn cubic(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self {
+                $new($(f32::cubic(p0.$field, p1.$field, p2.$field, p3.$field, t)),+)
+            }
+        }
+    };
+}
+
+interpolate_components!(Vec3, Vec3::new, x, y, z);
+interpolate_components!(Pos3, Pos3::new, x, y, z);
+interpolate_components!(Color, Color, 0, 1, 2);
+
+/// Rotations always slerp between the two closest keys, whatever the track's interpolation, and
+/// take the short way round. Keys for a full turn need to be less than half a turn apart.
+impl Interpolate for Quat {
+    fn lerp(a: Quat, b: Quat, t: f32) -> Quat {
+        a.slerp(b, t)
+    }
+
+    fn cubic(_: Quat, p1: Quat, p2: Quat, _: Quat, t: f32) -> Quat {
+        p1.slerp(p2, t)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe<T> {
+    /// In seconds
+    pub time: f32,
+    pub value: T,
+}
+
+/// A value that changes over time, passing through each of its keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Interpolate> Track<T> {
+    /// Sorts the keys by time. Panics if there aren't any.
+    pub fn new(mut keys: Vec<Keyframe<T>>, interpolation: Interpolation) -> Self {
+        assert!(!keys.is_empty(), "a track needs at least one key");
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            keys,
+            interpolation,
+        }
+    }
+
+    /// The same value at all times
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![Keyframe { time: 0.0, value }], Interpolation::Linear)
+    }
+
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    /// Holds the first and last values before and after the keys
+    pub fn sample(&self, time: f32) -> T {
+        let keys = &self.keys;
+        let next = keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return keys[0].value;
+        }
+        if next == keys.len() {
+            return keys[next - 1].value;
+        }
+
+        let (a, b) = (&keys[next - 1], &keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        match self.interpolation {
+            Interpolation::Linear => T::lerp(a.value, b.value, t),
+            Interpolation::Cubic => {
+                // the ends repeat the outermost key, so the curve stops at it
+                let before = keys[(next - 1).saturating_sub(1)].value;
+                let after = keys[(next + 1).min(keys.len() - 1)].value;
+                T::cubic(before, a.value, b.value, after, t)
+            }
+        }
+    }
+
+    /// The time of the last key
+    pub fn end(&self) -> f32 {
+        self.keys[self.keys.len() - 1].time
+    }
+}
+
+/// Translation, rotation and scale at one moment, applied in the reverse of that order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub translate: Vec3,
+    pub rotate: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self {
+            translate: Vec3::new(0.0, 0.0, 0.0),
+            rotate: Quat::IDENTITY,
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Pose {
+    pub fn to_matrix(&self) -> Mat4 {
+        let Pose {
+            translate: t,
+            rotate,
+            scale: s,
+        } = self;
+        Mat4::translation(t.x, t.y, t.z) * (rotate.to_matrix() * Mat4::scaling(s.x, s.y, s.z))
+    }
+
+    /// The inverse of `to_matrix`, built from the parts instead of inverting the whole matrix.
+    /// `None` when the scale is zero along any axis, which keys crossing from a positive to a
+    /// negative scale pass through.
+    pub fn inverse_matrix(&self) -> Option<Mat4> {
+        let Pose {
+            translate: t,
+            rotate,
+            scale: s,
+        } = self;
+        if s.iter().any(|c| c == 0.0) {
+            return None;
+        }
+
+        Some(
+            Mat4::scaling(1.0 / s.x, 1.0 / s.y, 1.0 / s.z)
+                * (rotate.to_matrix().transposed() * Mat4::translation(-t.x, -t.y, -t.z)),
+        )
+    }
+}
+
+impl Interpolate for Pose {
+    fn lerp(a: Pose, b: Pose, t: f32) -> Pose {
+        Pose {
+            translate: Vec3::lerp(a.translate, b.translate, t),
+            rotate: Quat::lerp(a.rotate, b.rotate, t),
+            scale: Vec3::lerp(a.scale, b.scale, t),
+        }
+    }
+
+    fn cubic(p0: Pose, p1: Pose, p2: Pose, p3: Pose, t: f32) -> Pose {
+        Pose {
+            translate: Vec3::cubic(p0.translate, p1.translate, p2.translate, p3.translate, t),
+            rotate: Quat::cubic(p0.rotate, p1.rotate, p2.rotate, p3.rotate, t),
+            scale: Vec3::cubic(p0.scale, p1.scale, p2.scale, p3.scale, t),
+        }
+    }
+}
+
+/// Moves a sphere around over time. The pose is applied after the sphere's own transform.
+#[derive(Debug, Clone)]
+pub struct TransformTrack {
+    // private so the cached inverse can't go stale, like `Sphere`'s matrices
+    base: Mat4,
+    base_inverse: Mat4,
+    poses: Track<Pose>,
+}
+
+impl TransformTrack {
+    /// `base` is the sphere's transform without any animation. Returns `None` if it can't be
+    /// inverted.
+    pub fn new(base: Mat4, poses: Track<Pose>) -> Option<Self> {
+        Some(Self {
+            base,
+            base_inverse: base.inverted()?,
+            poses,
+        })
+    }
+
+    pub fn base(&self) -> &Mat4 {
+        &self.base
+    }
+
+    pub fn poses(&self) -> &Track<Pose> {
+        &self.poses
+    }
+
+    pub fn matrix(&self, time: f32) -> Mat4 {
+        self.poses.sample(time).to_matrix() * self.base
+    }
+
+    /// The inverse of `matrix`, which is all a ray needs to test against the sphere at `time`.
+    /// `None` while the pose scales the sphere flat.
+    pub fn inverse_matrix(&self, time: f32) -> Option<Mat4> {
+        Some(self.base_inverse * self.poses.sample(time).inverse_matrix()?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightKey {
+    pub at: Pos3,
+    pub intensity: Color,
+}
+
+impl Interpolate for LightKey {
+    fn lerp(a: LightKey, b: LightKey, t: f32) -> LightKey {
+        LightKey {
+            at: Pos3::lerp(a.at, b.at, t),
+            intensity: Color::lerp(a.intensity, b.intensity, t),
+        }
+    }
+
+    fn cubic(p0: LightKey, p1: LightKey, p2: LightKey, p3: LightKey, t: f32) -> LightKey {
+        LightKey {
+            at: Pos3::cubic(p0.at, p1.at, p2.at, p3.at, t),
+            intensity: Color::cubic(p0.intensity, p1.intensity, p2.intensity, p3.intensity, t),
+        }
+    }
+}
+
+/// Where the camera is and what it's looking at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKey {
+    pub from: Pos3,
+    pub to: Pos3,
+}
+
+impl Interpolate for CameraKey {
+    fn lerp(a: CameraKey, b: CameraKey, t: f32) -> CameraKey {
+        CameraKey {
+            from: Pos3::lerp(a.from, b.from, t),
+            to: Pos3::lerp(a.to, b.to, t),
+        }
+    }
+
+    fn cubic(p0: CameraKey, p1: CameraKey, p2: CameraKey, p3: CameraKey, t: f32) -> CameraKey {
+        CameraKey {
+            from: Pos3::cubic(p0.from, p1.from, p2.from, p3.from, t),
+            to: Pos3::cubic(p0.to, p1.to, p2.to, p3.to, t),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CameraTrack {
+    pub keys: Track<CameraKey>,
+    pub up: Vec3,
+}
+
+/// Everything in a scene that changes over time, by index into `Scene::spheres` and
+/// `Scene::lights`
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    pub spheres: Vec<(usize, TransformTrack)>,
+    pub lights: Vec<(usize, Track<LightKey>)>,
+    pub camera: Option<CameraTrack>,
+}
+
+impl Animation {
+    pub fn is_empty(&self) -> bool {
+        self.spheres.is_empty() && self.lights.is_empty() && self.camera.is_none()
+    }
+
+    /// The time of the last key of any track
+    pub fn duration(&self) -> f32 {
+        let spheres = self.spheres.iter().map(|(_, track)| track.poses().end());
+        let lights = self.lights.iter().map(|(_, track)| track.end());
+        let camera = self.camera.iter().map(|track| track.keys.end());
+
+        spheres.chain(lights).chain(camera).fold(0.0, f32::max)
+    }
+
+    /// Moves everything animated to where it is at `time`. Spheres scaled flat at `time` keep
+    /// the pose they had before.
+    pub fn apply(&self, scene: &mut Scene, camera: &mut Viewport, time: f32) {
+        for (i, track) in &self.spheres {
+            let old = &scene.spheres[*i];
+            let Some(mut sphere) = Sphere::clone(old).try_set_transform(track.matrix(time)) else {
+                continue;
+            };
+            // with the shutter open, rays find the sphere wherever it's got to by then
+            sphere.motion = camera.has_motion_blur().then(|| Arc::new(track.clone()));
+            scene.spheres[*i] = Arc::new(sphere);
+        }
+
+        for (i, track) in &self.lights {
+            let key = track.sample(time);
+            let light = &mut scene.lights[*i];
+            light.position = key.at;
+            light.intensity = key.intensity;
+        }
+
+        if let Some(track) = &self.camera {
+            let key = track.keys.sample(time);
+            let moved = Viewport {
+                position: key.from,
+                ..camera.clone()
+            };
+            // keys where `up` lines up with the view keep the previous orientation
+            if let Some(moved) = moved.look_at(key.to, track.up) {
+                *camera = moved;
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_animation_tracks() {
+    use std::f32::consts::PI;
+
+    fn key<T>(time: f32, value: T) -> Keyframe<T> {
+        Keyframe { time, value }
+    }
+
+    let linear = Track::new(
+        vec![key(2.0, 10.0), key(0.0, 0.0), key(3.0, 0.0)],
+        Interpolation::Linear,
+    );
+    assert_eq!(linear.sample(-1.0), 0.0);
+    assert_eq!(linear.sample(1.0), 5.0);
+    assert_eq!(linear.sample(2.5), 5.0);
+    assert_eq!(linear.sample(9.0), 0.0);
+
+    // the spline passes through every key, and overshoots between them where a line wouldn't
+    let cubic = Track {
+        interpolation: Interpolation::Cubic,
+        ..linear.clone()
+    };
+    for k in cubic.keys() {
+        assert_eq!(cubic.sample(k.time), k.value);
+    }
+    assert!(cubic.sample(1.9) > linear.sample(1.9));
+
+    // a turntable: a third of a turn per second
+    let turn = |time: f32| {
+        let pose = Pose {
+            rotate: Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), time * 2.0 * PI / 3.0),
+            ..Default::default()
+        };
+        key(time, pose)
+    };
+    let base = Mat4::translation(1.0, 0.0, 0.0);
+    let track = TransformTrack::new(
+        base,
+        Track::new(
+            vec![turn(0.0), turn(1.0), turn(2.0), turn(3.0)],
+            Interpolation::Cubic,
+        ),
+    )
+    .unwrap();
+    assert_eq!(track.matrix(0.75), Mat4::rotation_y(PI / 2.0) * base);
+    assert_eq!(track.matrix(3.0), base.clone());
+    for time in [0.4, 1.5, 2.9] {
+        assert_eq!(track.inverse_matrix(time), track.matrix(time).inverted());
+    }
+    assert_eq!(
+        track.matrix(1.5) * Pos3::new(0.0, 0.0, 0.0),
+        Pos3::new(-1.0, 0.0, 0.0)
+    );
+}
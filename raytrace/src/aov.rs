@@ -0,0 +1,215 @@
+use std::{path::Path, sync::Arc};
+
+use image::ImageResult;
+
+use crate::{scene::Intersection, Color, Framebuffer, Object, Pos3, Ray, Scene, Vec3};
+
+/// Arbitrary output variables, the auxiliary buffers written alongside the beauty pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aov {
+    /// Distance from the camera ray's origin
+    Depth,
+    /// World-space shading normal
+    Normal,
+    /// Surface color with any texture applied, before lighting
+    Albedo,
+    ObjectId,
+    MaterialId,
+    /// World-space hit point
+    Position,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 6] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Position,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Position => "position",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+}
+
+/// Everything known about the closest hit of a single camera ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovSample {
+    /// `f32::INFINITY` when the ray hits nothing
+    pub depth: f32,
+    pub normal: Vec3,
+    pub albedo: Color,
+    /// Index into `Scene::spheres`
+    pub object_id: Option<usize>,
+    /// `Material::id` of the sphere that was hit
+    pub material_id: Option<usize>,
+    pub position: Pos3,
+}
+
+impl AovSample {
+    pub const MISS: AovSample = AovSample {
+        depth: f32::INFINITY,
+        normal: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        albedo: Color::BLACK,
+        object_id: None,
+        material_id: None,
+        position: Pos3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+    };
+
+    /// Packs one variable into a color. Scalars are repeated across all channels and IDs are
+    /// offset by one, so that 0.0 means nothing was hit.
+    pub fn value(&self, aov: Aov) -> Color {
+        let id = |id: Option<usize>| {
+            let v = id.map_or(0.0, |id| (id + 1) as f32);
+            Color(v, v, v)
+        };
+
+        match aov {
+            Aov::Depth => Color(self.depth, self.depth, self.depth),
+            Aov::Normal => Color(self.normal.x, self.normal.y, self.normal.z),
+            Aov::Albedo => self.albedo,
+            Aov::ObjectId => id(self.object_id),
+            Aov::MaterialId => id(self.material_id),
+            Aov::Position => Color(self.position.x, self.position.y, self.position.z),
+        }
+    }
+}
+
+/// The closest hit of a camera ray, handed back by the integrators alongside its color so the
+/// variables come from the same intersection as the beauty pass
+#[derive(Debug, Clone)]
+pub struct PrimaryHit {
+    /// The ray as the integrator traced it
+    pub ray: Ray,
+    pub hit: Option<Intersection>,
+}
+
+impl Scene {
+    /// Reads the attributes of a hit found by one of the integrators
+    pub fn aov_sample(&self, primary: &PrimaryHit) -> AovSample {
+        let PrimaryHit { ray, hit } = primary;
+        let Some(hit) = hit else {
+            return AovSample::MISS;
+        };
+
+        let Object::Sphere(obj) = &hit.obj;
+        let point = ray.position(hit.t);
+        let object_id = self.spheres.iter().position(|s| Arc::ptr_eq(s, obj));
+        let obj = obj.at_time(ray.time);
+
+        AovSample {
+            depth: hit.t * ray.dir.magnitude(),
+            normal: obj.shading_normal_at(point),
+            albedo: obj.material_at(point).color,
+            object_id,
+            material_id: obj.material.id,
+            position: point,
+        }
+    }
+}
+
+/// One `Framebuffer` per requested variable
+#[derive(Debug, Clone)]
+pub struct AovBuffers {
+    buffers: Vec<(Aov, Framebuffer)>,
+}
+
+impl AovBuffers {
+    pub fn new(width: usize, height: usize, aovs: &[Aov]) -> Self {
+        let mut buffers: Vec<(Aov, Framebuffer)> = Vec::new();
+        for aov in aovs {
+            if !buffers.iter().any(|(a, _)| a == aov) {
+                buffers.push((*aov, Framebuffer::new(width, height)));
+            }
+        }
+
+        Self { buffers }
+    }
+
+    pub fn get(&self, aov: Aov) -> Option<&Framebuffer> {
+        self.buffers.iter().find(|(a, _)| *a == aov).map(|(_, b)| b)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Aov, &Framebuffer)> {
+        self.buffers.iter().map(|(a, b)| (*a, b))
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, sample: &AovSample) {
+        for (aov, buffer) in self.buffers.iter_mut() {
+            buffer.set(x, y, sample.value(*aov));
+        }
+    }
+
+    /// Writes each buffer next to `beauty`, e.g. `out.exr` becomes `out.depth.exr`. Use a float
+    /// format like `.exr` to keep depth, positions and IDs intact.
+    pub fn save(&self, beauty: impl AsRef<Path>) -> ImageResult<()> {
+        let beauty = beauty.as_ref();
+        let stem = beauty.file_stem().and_then(|s| s.to_str()).unwrap_or("aov");
+        let extension = beauty.extension().and_then(|e| e.to_str()).unwrap_or("exr");
+
+        for (aov, buffer) in self.iter() {
+            buffer.save(beauty.with_file_name(format!("{stem}.{}.{extension}", aov.name())))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_aov_sample() {
+    use crate::{identity_matrix, objects::material::Material, Mat4};
+
+    // the last sphere looks the same as the first, but doesn't share its material
+    let red = Material::new(Color(1.0, 0.0, 0.0), 0.1, 0.9, 0.0, 1.0);
+    let scene = Scene {
+        spheres: vec![
+            crate::objects::Sphere::new(Mat4::translation(0.0, 0.0, 5.0), red.clone().with_id(0))
+                .into(),
+            crate::objects::Sphere::new(identity_matrix!(), Material::default().with_id(1)).into(),
+            crate::objects::Sphere::new(Mat4::translation(0.0, 0.0, 2.0), red.with_id(2)).into(),
+        ],
+        ..Default::default()
+    };
+
+    let ray = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0));
+    let (_, primary) = scene.trace_ray_hit(ray.clone(), 0.0, f32::MAX, 0);
+    let sample = scene.aov_sample(&primary);
+    assert_eq!(sample.depth, 4.0);
+    assert_eq!(sample.position, Pos3::new(0.0, 0.0, -1.0));
+    assert_eq!(sample.normal, Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(sample.object_id, Some(1));
+    assert_eq!(sample.material_id, Some(1));
+
+    // skip past the middle sphere to hit the last one
+    let (_, primary) = scene.trace_ray_hit(ray, 3.25, f32::MAX, 0);
+    let sample = scene.aov_sample(&primary);
+    assert_eq!(sample.albedo, Color(1.0, 0.0, 0.0));
+    assert_eq!(sample.object_id, Some(2));
+    assert_eq!(sample.material_id, Some(2));
+    assert_eq!(sample.value(Aov::ObjectId), Color(3.0, 3.0, 3.0));
+
+    let miss = Ray::new(Pos3::new(0.0, 5.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    let (_, primary) = scene.trace_ray_hit(miss, 0.0, f32::MAX, 0);
+    assert_eq!(scene.aov_sample(&primary), AovSample::MISS);
+}
@@ -0,0 +1,449 @@
+//! Command-line options for the `raytrace` binary
+
+use std::{fmt, ops::Range, path::PathBuf, str::FromStr};
+
+use crate::{
+    aov::Aov,
+    palette::Dither,
+    render::{Integrator, RenderSettings},
+    scene_file::SceneFile,
+    sequence::SequenceSettings,
+    tonemap::{ToneMap, ToneMapper},
+};
+
+pub const USAGE: &str = "\
+Usage: raytrace [OPTIONS] <SCENE>
+
+Renders a YAML scene file to an image. Pass `-` as the scene to read it from stdin.
+
+Options:
+  -o, --output <PATH>       Where to save the image [default: out.png]
+                            The extension picks the format: png, jpg, hdr, pfm, exr, ...
+                            With `--frames`, gif and apng save every frame in one animation
+  -W, --width <PIXELS>      Image width, overriding the scene file
+  -H, --height <PIXELS>     Image height, overriding the scene file
+  -s, --samples <N>         Samples per pixel [default: 1]
+  -d, --max-depth <N>       Bounces for the path tracer, or reflection depth [default: 3]
+  -j, --threads <N>         Worker threads [default: one per core]
+  -i, --integrator <NAME>   whitted, path or ao [default: whitted]
+  -a, --aov <NAMES>         Comma-separated AOVs to save as EXR next to the image, or `all`:
+                            depth, normal, albedo, object_id, material_id, position
+      --seed <N>            Seed for the random samples [default: 0]
+  -f, --frames <RANGE>      Render an animation, as a single frame like `12` or a range like
+                            `0..48` (not including 48). Each frame is saved to the output path
+                            with its number in place of a run of `#`, or before the extension.
+      --fps <N>             Frames per second of animation [default: 24]
+      --shutter <FRACTION>  Blur motion over this much of each frame, from 0 to 1, overriding
+                            the scene file's shutter
+      --dither              Dither GIFs instead of snapping to the nearest palette color
+  -t, --tonemap <NAME>      How bright colors are fit into 8-bit images and the preview
+                            window: none (clips), reinhard or aces [default: aces]
+  -e, --exposure <STOPS>    Brighten (or darken, below 0) before tone mapping [default: 0]
+  -p, --preview             Show the render in a window with orbit controls, saving the
+                            last finished image once it's closed
+  -w, --watch               Re-render whenever the scene file changes, until interrupted
+      --preview-scale <N>   In watch mode, divide the resolution by this [default: 4]
+  -h, --help                Print this message
+";
+
+/// Everything needed to run one render from the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub scene: PathBuf,
+    pub output: PathBuf,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub samples: u32,
+    pub max_depth: usize,
+    /// `None` leaves it to rayon
+    pub threads: Option<usize>,
+    pub integrator: Integrator,
+    pub aovs: Vec<Aov>,
+    pub seed: u64,
+    /// Frames to render from the scene's animation
+    pub frames: Option<Range<u32>>,
+    pub fps: f32,
+    /// How much of each frame the shutter stays open for
+    pub shutter: Option<f32>,
+    /// How GIF output is reduced to its palette
+    pub dither: Dither,
+    /// Applied to everything saved with 8 bits per channel, and to the preview window
+    pub tonemap: ToneMapper,
+    pub preview: bool,
+    pub watch: bool,
+    /// Resolution divisor for the renders in watch mode
+    pub preview_scale: usize,
+}
+
+impl Options {
+    fn new(scene: PathBuf) -> Self {
+        let defaults = RenderSettings::default();
+        Self {
+            scene,
+            output: PathBuf::from("out.png"),
+            width: None,
+            height: None,
+            samples: defaults.samples,
+            max_depth: defaults.max_depth,
+            threads: None,
+            integrator: defaults.integrator,
+            aovs: Vec::new(),
+            seed: defaults.seed,
+            frames: None,
+            fps: 24.0,
+            shutter: None,
+            dither: Dither::None,
+            tonemap: ToneMapper::default(),
+            preview: false,
+            watch: false,
+            preview_scale: 4,
+        }
+    }
+
+    /// Whether the scene is read from stdin, given as `-`
+    pub fn reads_stdin(&self) -> bool {
+        self.scene.as_os_str() == "-"
+    }
+
+    /// The scene file's settings with the command-line overrides applied
+    pub fn render_settings(&self, file: &SceneFile) -> RenderSettings {
+        RenderSettings {
+            width: self.width.unwrap_or(file.width),
+            height: self.height.unwrap_or(file.height),
+            samples: self.samples,
+            max_depth: self.max_depth,
+            integrator: self.integrator,
+            seed: self.seed,
+            ..file.render_settings()
+        }
+    }
+
+    /// Time into the animation, in seconds
+    pub fn frame_time(&self, frame: u32) -> f32 {
+        frame as f32 / self.fps
+    }
+
+    /// The scene file's shutter, or the one given on the command line, in seconds from the
+    /// start of a frame
+    pub fn shutter(&self, file: &SceneFile) -> (f32, f32) {
+        match self.shutter {
+            Some(fraction) => (0.0, fraction / self.fps),
+            None => file.shutter,
+        }
+    }
+
+    /// How a GIF or APNG of every frame is saved
+    pub fn sequence_settings(&self) -> SequenceSettings {
+        SequenceSettings {
+            fps: self.fps,
+            dither: self.dither,
+            ..Default::default()
+        }
+    }
+
+    /// Where `frame` is saved. Numbers are padded to the width of the run of `#` in the output
+    /// file name, or to 4 digits when it has none.
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        let name = self
+            .output
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let name = match name.find('#') {
+            Some(start) => {
+                let width = name[start..].chars().take_while(|&c| c == '#').count();
+                let end = start + width;
+                format!("{}{frame:0width$}{}", &name[..start], &name[end..])
+            }
+            None => match name.rsplit_once('.') {
+                Some((stem, extension)) => format!("{stem}_{frame:04}.{extension}"),
+                None => format!("{name}_{frame:04}"),
+            },
+        };
+
+        self.output.with_file_name(name)
+    }
+
+    /// Like `render_settings`, at the lower resolution used in watch mode
+    pub fn preview_settings(&self, file: &SceneFile) -> RenderSettings {
+        let settings = self.render_settings(file);
+        RenderSettings {
+            width: (settings.width / self.preview_scale).max(1),
+            height: (settings.height / self.preview_scale).max(1),
+            ..settings
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Render(Options),
+    Help,
+}
+
+/// Invalid command-line arguments. The message is meant to be shown as is, followed by `USAGE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Parses the arguments after the program name. Options take their value either as the next
+/// argument or after an `=`, as in `--samples=16`.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, UsageError> {
+    let mut args = args.into_iter();
+    let mut scene = None;
+    let mut options = Options::new(PathBuf::new());
+
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        }
+
+        if !arg.starts_with('-') || arg == "-" {
+            if scene.is_some() {
+                return Err(UsageError(format!("unexpected argument `{arg}`")));
+            }
+            scene = Some(PathBuf::from(arg));
+            continue;
+        }
+
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| UsageError(format!("`{flag}` needs a value")))
+        };
+
+        match flag.as_str() {
+            "-o" | "--output" => options.output = PathBuf::from(value()?),
+            "-W" | "--width" => options.width = Some(positive(&flag, &value()?)?),
+            "-H" | "--height" => options.height = Some(positive(&flag, &value()?)?),
+            "-s" | "--samples" => options.samples = positive(&flag, &value()?)?,
+            "-d" | "--max-depth" => options.max_depth = number(&flag, &value()?)?,
+            "-j" | "--threads" => options.threads = Some(positive(&flag, &value()?)?),
+            "-i" | "--integrator" => {
+                let name = value()?;
+                options.integrator = Integrator::from_name(&name).ok_or_else(|| {
+                    UsageError(format!(
+                        "unknown integrator `{name}`, expected one of: {}",
+                        Integrator::ALL.map(|i| i.name()).join(", ")
+                    ))
+                })?;
+            }
+            "-a" | "--aov" => {
+                let names = value()?;
+                for name in names.split(',').map(str::trim) {
+                    let aovs = if name == "all" {
+                        Aov::ALL.to_vec()
+                    } else {
+                        vec![Aov::from_name(name).ok_or_else(|| {
+                            UsageError(format!(
+                                "unknown AOV `{name}`, expected `all` or any of: {}",
+                                Aov::ALL.map(|aov| aov.name()).join(", ")
+                            ))
+                        })?]
+                    };
+
+                    for aov in aovs {
+                        if !options.aovs.contains(&aov) {
+                            options.aovs.push(aov);
+                        }
+                    }
+                }
+            }
+            "--seed" => options.seed = number(&flag, &value()?)?,
+            "-f" | "--frames" => {
+                let range = value()?;
+                let frames = match range.split_once("..") {
+                    Some((start, end)) => number(&flag, start)?..number(&flag, end)?,
+                    None => {
+                        let frame = number(&flag, &range)?;
+                        frame..frame + 1
+                    }
+                };
+                if frames.is_empty() {
+                    return Err(UsageError(format!("`{flag}` range `{range}` is empty")));
+                }
+                options.frames = Some(frames);
+            }
+            "--fps" => {
+                let fps = value()?;
+                options.fps = match fps.parse::<f32>() {
+                    Ok(n) if n > 0.0 && n.is_finite() => n,
+                    _ => {
+                        return Err(UsageError(format!(
+                            "`{flag}` expects a positive number, got `{fps}`"
+                        )))
+                    }
+                };
+            }
+            "--shutter" => {
+                let shutter = value()?;
+                options.shutter = match shutter.parse::<f32>() {
+                    Ok(n) if (0.0..=1.0).contains(&n) => Some(n),
+                    _ => {
+                        return Err(UsageError(format!(
+                            "`{flag}` expects a fraction of a frame from 0 to 1, got `{shutter}`"
+                        )))
+                    }
+                };
+            }
+            "--dither" => options.dither = Dither::FloydSteinberg,
+            "-t" | "--tonemap" => {
+                let name = value()?;
+                options.tonemap.operator = ToneMap::from_name(&name).ok_or_else(|| {
+                    UsageError(format!(
+                        "unknown tone mapping `{name}`, expected one of: {}",
+                        ToneMap::NAMED.map(|op| op.name()).join(", ")
+                    ))
+                })?;
+            }
+            "-e" | "--exposure" => {
+                let stops = value()?;
+                options.tonemap.exposure = match stops.parse::<f32>() {
+                    Ok(n) if n.is_finite() => n,
+                    _ => {
+                        return Err(UsageError(format!(
+                            "`{flag}` expects a number of stops, got `{stops}`"
+                        )))
+                    }
+                };
+            }
+            "-p" | "--preview" => options.preview = true,
+            "-w" | "--watch" => options.watch = true,
+            "--preview-scale" => options.preview_scale = positive(&flag, &value()?)?,
+            _ => return Err(UsageError(format!("unknown option `{flag}`"))),
+        }
+    }
+
+    if options.watch && options.preview {
+        return Err(UsageError(
+            "`--watch` and `--preview` can't be used together".to_string(),
+        ));
+    }
+    if options.frames.is_some() && (options.watch || options.preview) {
+        return Err(UsageError(
+            "`--frames` can't be used with `--watch` or `--preview`".to_string(),
+        ));
+    }
+
+    options.scene = scene.ok_or_else(|| UsageError("no scene file given".to_string()))?;
+    if options.watch && options.reads_stdin() {
+        return Err(UsageError(
+            "`--watch` needs a scene file to watch, not `-`".to_string(),
+        ));
+    }
+
+    Ok(Command::Render(options))
+}
+
+fn number<T: FromStr>(flag: &str, value: &str) -> Result<T, UsageError> {
+    value
+        .parse()
+        .map_err(|_| UsageError(format!("`{flag}` expects a whole number, got `{value}`")))
+}
+
+fn positive<T: FromStr + Default + PartialEq>(flag: &str, value: &str) -> Result<T, UsageError> {
+    let n = number(flag, value)?;
+    if n == T::default() {
+        return Err(UsageError(format!("`{flag}` must be at least 1")));
+    }
+
+    Ok(n)
+}
+
+#[test]
+pub fn test_cli_parse() {
+    let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+    let Command::Render(options) = parse(args(
+        "scene.yml -o out.exr --width=320 -H 240 -s 16 -d 5 -j 2 -i path --aov depth,normal -a all",
+    ))
+    .unwrap() else {
+        panic!("expected a render command");
+    };
+    assert_eq!(options.scene, PathBuf::from("scene.yml"));
+    assert_eq!(options.output, PathBuf::from("out.exr"));
+    assert_eq!((options.width, options.height), (Some(320), Some(240)));
+    assert_eq!((options.samples, options.max_depth), (16, 5));
+    assert_eq!(options.threads, Some(2));
+    assert_eq!(options.integrator, Integrator::Path);
+    assert_eq!(&options.aovs[..2], &[Aov::Depth, Aov::Normal]);
+    assert_eq!(options.aovs.len(), Aov::ALL.len());
+
+    // the scene file's resolution is used unless it's overridden
+    let settings = options.render_settings(&SceneFile::default());
+    assert_eq!((settings.width, settings.height), (320, 240));
+    assert!(!options.watch && !options.preview);
+    assert_eq!(options.frames, None);
+    assert_eq!(options.tonemap, ToneMapper::default());
+
+    let Command::Render(options) = parse(args(
+        "scene.yml -f 10..12 --fps 5 --shutter 0.5 -o out/spin_###.png -t none --exposure -1.5",
+    ))
+    .unwrap() else {
+        panic!("expected a render command");
+    };
+    assert_eq!(options.tonemap, ToneMapper::new(ToneMap::Clamp, -1.5));
+    assert_eq!(options.frames, Some(10..12));
+    assert_eq!(options.frame_time(11), 2.2);
+    assert_eq!(options.shutter(&SceneFile::default()), (0.0, 0.1));
+    assert_eq!(options.dither, Dither::None);
+    assert_eq!(options.sequence_settings().fps, 5.0);
+    assert_eq!(options.frame_path(11), PathBuf::from("out/spin_011.png"));
+    let options = Options {
+        output: PathBuf::from("clip.exr"),
+        ..options
+    };
+    assert_eq!(options.frame_path(7), PathBuf::from("clip_0007.exr"));
+
+    let Command::Render(options) = parse(args("scene.yml -w --preview-scale 8 --dither")).unwrap()
+    else {
+        panic!("expected a render command");
+    };
+    assert!(options.watch);
+    assert_eq!(options.dither, Dither::FloydSteinberg);
+    let settings = options.preview_settings(&SceneFile::default());
+    assert_eq!((settings.width, settings.height), (125, 125));
+
+    assert_eq!(parse(args("scene.yml --help")), Ok(Command::Help));
+
+    let error = |s: &str| parse(args(s)).unwrap_err().0;
+    assert_eq!(error(""), "no scene file given");
+    assert_eq!(error("a.yml b.yml"), "unexpected argument `b.yml`");
+    assert_eq!(error("a.yml --fast"), "unknown option `--fast`");
+    assert_eq!(error("a.yml -s"), "`-s` needs a value");
+    assert_eq!(error("a.yml -s 0"), "`-s` must be at least 1");
+    assert_eq!(error("a.yml -f 5..5"), "`-f` range `5..5` is empty");
+    assert_eq!(
+        error("a.yml -w -p"),
+        "`--watch` and `--preview` can't be used together"
+    );
+    assert_eq!(
+        error("- -w"),
+        "`--watch` needs a scene file to watch, not `-`"
+    );
+    assert_eq!(
+        error("a.yml --width=wide"),
+        "`--width` expects a whole number, got `wide`"
+    );
+    assert_eq!(
+        error("a.yml -t filmic"),
+        "unknown tone mapping `filmic`, expected one of: none, reinhard, aces"
+    );
+    assert_eq!(
+        error("a.yml -i photon"),
+        "unknown integrator `photon`, expected one of: whitted, path, ao"
+    );
+}
@@ -0,0 +1,194 @@
+use rayon::prelude::{IndexedParallelIterator, ParallelIterator, ParallelSliceMut};
+
+use crate::{Color, Framebuffer};
+
+/// B3 spline weights, applied separably over a 5x5 footprint
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Albedo below this is treated as black when removing it from the color
+const ALBEDO_EPSILON: f32 = 0.001;
+
+/// Edge-avoiding à-trous wavelet filter. Each pass blurs with a wider, sparser kernel, and
+/// neighbours only contribute when their color, normal and albedo are similar to the center's, so
+/// noise gets smoothed away while object edges and texture detail survive.
+///
+/// The feature buffers are the `Aov::Normal` and `Aov::Albedo` outputs for the same frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    /// Number of passes. Each doubles the kernel's reach, so 5 covers about 80 pixels.
+    pub iterations: u32,
+    /// How different two colors can be before they stop blending. Halves every pass, so the
+    /// finer details of later passes don't get smeared.
+    pub sigma_color: f32,
+    /// As `sigma_color`, for the distance between world-space normals
+    pub sigma_normal: f32,
+    /// As `sigma_color`, for the difference between albedos
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 1.0,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+fn distance_squared(a: Color, b: Color) -> f32 {
+    let d = a - b;
+    (d.0 * d.0) + (d.1 * d.1) + (d.2 * d.2)
+}
+
+/// Splits out the part of the color that comes from the surface, so texture detail doesn't get
+/// filtered as if it were noise
+fn demodulate(color: Color, albedo: Color) -> Color {
+    let channel = |c: f32, a: f32| if a > ALBEDO_EPSILON { c / a } else { c };
+    Color(
+        channel(color.0, albedo.0),
+        channel(color.1, albedo.1),
+        channel(color.2, albedo.2),
+    )
+}
+
+fn remodulate(color: Color, albedo: Color) -> Color {
+    let channel = |c: f32, a: f32| if a > ALBEDO_EPSILON { c * a } else { c };
+    Color(
+        channel(color.0, albedo.0),
+        channel(color.1, albedo.1),
+        channel(color.2, albedo.2),
+    )
+}
+
+impl Denoiser {
+    pub fn denoise(
+        &self,
+        color: &Framebuffer,
+        normal: &Framebuffer,
+        albedo: &Framebuffer,
+    ) -> Framebuffer {
+        let (width, height) = (color.width(), color.height());
+        assert!(
+            [normal, albedo]
+                .iter()
+                .all(|b| b.width() == width && b.height() == height),
+            "feature buffers must be the same size as the color buffer"
+        );
+
+        let mut current: Vec<Color> = color
+            .pixels()
+            .iter()
+            .zip(albedo.pixels())
+            .map(|(c, a)| demodulate(*c, *a))
+            .collect();
+        let mut next = vec![Color::BLACK; current.len()];
+
+        for i in 0..self.iterations {
+            let step = 1isize << i;
+            let sigma_color = self.sigma_color / (1 << i) as f32;
+            let inv_color = 1.0 / (sigma_color * sigma_color).max(f32::EPSILON);
+            let inv_normal = 1.0 / (self.sigma_normal * self.sigma_normal).max(f32::EPSILON);
+            let inv_albedo = 1.0 / (self.sigma_albedo * self.sigma_albedo).max(f32::EPSILON);
+
+            let source = &current;
+            next.par_chunks_mut(width.max(1))
+                .enumerate()
+                .for_each(|(y, row)| {
+                    for (x, out) in row.iter_mut().enumerate() {
+                        let center = (y * width) + x;
+                        let (c_p, n_p, a_p) = (
+                            source[center],
+                            normal.pixels()[center],
+                            albedo.pixels()[center],
+                        );
+
+                        let mut sum = Color::BLACK;
+                        let mut total = 0.0;
+
+                        for (ky, wy) in KERNEL.iter().enumerate() {
+                            let qy = y as isize + ((ky as isize - 2) * step);
+                            if qy < 0 || qy >= height as isize {
+                                continue;
+                            }
+
+                            for (kx, wx) in KERNEL.iter().enumerate() {
+                                let qx = x as isize + ((kx as isize - 2) * step);
+                                if qx < 0 || qx >= width as isize {
+                                    continue;
+                                }
+
+                                let q = (qy as usize * width) + qx as usize;
+                                let c_q = source[q];
+
+                                let exponent = (distance_squared(c_p, c_q) * inv_color)
+                                    + (distance_squared(n_p, normal.pixels()[q]) * inv_normal)
+                                    + (distance_squared(a_p, albedo.pixels()[q]) * inv_albedo);
+                                let weight = wx * wy * (-exponent).exp();
+
+                                sum = sum + (c_q * weight);
+                                total += weight;
+                            }
+                        }
+
+                        // the center always contributes, so `total` is never 0
+                        *out = sum * (1.0 / total);
+                    }
+                });
+
+            std::mem::swap(&mut current, &mut next);
+        }
+
+        let pixels = current
+            .into_iter()
+            .zip(albedo.pixels())
+            .map(|(c, a)| remodulate(c, *a))
+            .collect();
+
+        Framebuffer::from_pixels(width, height, pixels)
+    }
+}
+
+#[test]
+pub fn test_denoise() {
+    use crate::sampling::Rng;
+
+    // left half faces one way and right half another, with the same noisy gray on both
+    let (width, height) = (16, 8);
+    let mut rng = Rng::new(7, 0);
+    let mut noisy = Framebuffer::new(width, height);
+    let mut normal = Framebuffer::new(width, height);
+    let albedo = Framebuffer::from_pixels(width, height, vec![Color::WHITE; width * height]);
+
+    for y in 0..height {
+        for x in 0..width {
+            let (n, base) = if x < width / 2 {
+                (Color(0.0, 0.0, -1.0), 0.2)
+            } else {
+                (Color(1.0, 0.0, 0.0), 0.8)
+            };
+            normal.set(x, y, n);
+            let v = base + ((rng.next_f32() - 0.5) * 0.2);
+            noisy.set(x, y, Color(v, v, v));
+        }
+    }
+
+    let error = |buffer: &Framebuffer| -> f32 {
+        let mut error = 0.0;
+        for y in 0..height {
+            for x in 0..width {
+                let expected = if x < width / 2 { 0.2 } else { 0.8 };
+                error += (buffer.get(x, y).0 - expected).powi(2);
+            }
+        }
+        error
+    };
+
+    let denoised = Denoiser::default().denoise(&noisy, &normal, &albedo);
+    assert!(error(&denoised) < error(&noisy) * 0.25);
+
+    // the normal edge keeps the two halves from bleeding into each other
+    assert!((denoised.get(width / 2 - 1, 4).0 - 0.2).abs() < 0.05);
+    assert!((denoised.get(width / 2, 4).0 - 0.8).abs() < 0.05);
+}
@@ -0,0 +1,209 @@
+use std::{f32::consts::PI, path::Path, sync::Arc};
+
+use image::ImageResult;
+
+use crate::{
+    objects::texture::{Filter, ImageTexture},
+    sampling::{uniform_sphere, Distribution2D, Rng},
+    sky::Sky,
+    Color, DirectionalLight, Vec3,
+};
+
+/// Light arriving from infinitely far away, seen by any ray that doesn't hit an object
+#[derive(Debug, Clone)]
+pub enum Environment {
+    Constant(Color),
+    /// Blends from `horizon` up to `zenith` above the horizon and down to `ground` below it
+    Gradient {
+        zenith: Color,
+        horizon: Color,
+        ground: Color,
+    },
+    Map(EnvironmentMap),
+    /// Procedural daylight, which also lights the scene with its sun
+    Sky(Sky),
+}
+
+/// A direction sampled from the environment along with the light arriving from it
+#[derive(Debug, Clone, Copy)]
+pub struct EnvironmentSample {
+    pub dir: Vec3,
+    pub radiance: Color,
+    /// With respect to solid angle
+    pub pdf: f32,
+}
+
+impl Environment {
+    /// Returns the light arriving from direction `dir`, which doesn't need to be normalized
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        match self {
+            Environment::Constant(color) => *color,
+            Environment::Gradient {
+                zenith,
+                horizon,
+                ground,
+            } => {
+                let y = dir.to_normalized().y;
+                if y >= 0.0 {
+                    (*horizon * (1.0 - y)) + (*zenith * y)
+                } else {
+                    (*horizon * (1.0 + y)) + (*ground * -y)
+                }
+            }
+            Environment::Map(map) => map.radiance(dir),
+            Environment::Sky(sky) => sky.radiance(dir),
+        }
+    }
+
+    /// Returns the environment's sun if it has one. It's kept separate from `radiance` since
+    /// it's too small and bright to be found by sampling directions.
+    pub fn sun(&self) -> Option<DirectionalLight> {
+        match self {
+            Environment::Sky(sky) => sky.sun(),
+            _ => None,
+        }
+    }
+
+    /// Picks a direction to gather light from, favoring bright regions when backed by an image
+    pub fn sample(&self, rng: &mut Rng) -> EnvironmentSample {
+        match self {
+            Environment::Map(map) => map.sample(rng),
+            _ => {
+                let dir = uniform_sphere(rng);
+                EnvironmentSample {
+                    dir,
+                    radiance: self.radiance(dir),
+                    pdf: 1.0 / (4.0 * PI),
+                }
+            }
+        }
+    }
+
+    /// The pdf that `sample` would have returned `dir`, with respect to solid angle
+    pub fn pdf(&self, dir: Vec3) -> f32 {
+        match self {
+            Environment::Map(map) => map.pdf(dir),
+            _ => 1.0 / (4.0 * PI),
+        }
+    }
+}
+
+/// An equirectangular (latitude/longitude) image wrapped around the scene. The center of the
+/// image faces +Z and the top row is straight up.
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    pub image: Arc<ImageTexture>,
+    pub intensity: f32,
+    distribution: Arc<Distribution2D>,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: ImageTexture, intensity: f32) -> Self {
+        let width = image.width();
+        let height = image.height();
+
+        // rows near the poles cover less solid angle, so they're weighted down by sin(theta)
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+            for x in 0..width {
+                weights.push(image.texel(x as isize, y as isize).luminance() * sin_theta);
+            }
+        }
+
+        Self {
+            image: Arc::new(image.with_filter(Filter::Bilinear)),
+            intensity,
+            distribution: Arc::new(Distribution2D::new(&weights, width, height)),
+        }
+    }
+
+    /// Supports anything the `image` crate can decode, including Radiance `.hdr` and OpenEXR
+    pub fn open(path: impl AsRef<Path>, intensity: f32) -> ImageResult<Self> {
+        Ok(Self::new(ImageTexture::open(path)?, intensity))
+    }
+
+    /// Returns (u, v) in image space, where v = 0 is the top row
+    fn dir_to_uv(dir: Vec3) -> (f32, f32) {
+        let dir = dir.to_normalized();
+        let u = (dir.x.atan2(dir.z) / (2.0 * PI)) + 0.5;
+        let v = dir.y.clamp(-1.0, 1.0).acos() / PI;
+
+        (u, v)
+    }
+
+    fn uv_to_dir(u: f32, v: f32) -> Vec3 {
+        let phi = (u - 0.5) * 2.0 * PI;
+        let theta = v * PI;
+        let (sin_t, cos_t) = theta.sin_cos();
+        let (sin_p, cos_p) = phi.sin_cos();
+
+        Vec3::new(sin_t * sin_p, cos_t, sin_t * cos_p)
+    }
+
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        let (u, v) = Self::dir_to_uv(dir);
+        self.image.sample(u, 1.0 - v) * self.intensity
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> EnvironmentSample {
+        let ((u, v), pdf_uv) = self.distribution.sample(rng.next_f32(), rng.next_f32());
+        let dir = Self::uv_to_dir(u, v);
+        let sin_theta = (v * PI).sin();
+
+        let pdf = if sin_theta == 0.0 {
+            0.0
+        } else {
+            pdf_uv / (2.0 * PI * PI * sin_theta)
+        };
+
+        EnvironmentSample {
+            dir,
+            radiance: self.radiance(dir),
+            pdf,
+        }
+    }
+
+    pub fn pdf(&self, dir: Vec3) -> f32 {
+        let (u, v) = Self::dir_to_uv(dir);
+        let sin_theta = (v * PI).sin();
+
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[test]
+pub fn test_environment_gradient() {
+    let env = Environment::Gradient {
+        zenith: Color::BLUE,
+        horizon: Color::WHITE,
+        ground: Color::BLACK,
+    };
+
+    assert_eq!(env.radiance(Vec3::new(0.0, 1.0, 0.0)), Color::BLUE);
+    assert_eq!(env.radiance(Vec3::new(1.0, 0.0, 0.0)), Color::WHITE);
+    assert_eq!(env.radiance(Vec3::new(0.0, -2.0, 0.0)), Color::BLACK);
+}
+
+#[test]
+pub fn test_environment_map_importance() {
+    // a single bright texel should attract nearly every sample
+    let mut pixels = vec![Color(0.001, 0.001, 0.001); 8 * 4];
+    pixels[8 + 2] = Color(100.0, 100.0, 100.0);
+
+    let map = EnvironmentMap::new(ImageTexture::from_pixels(8, 4, pixels), 1.0);
+    let mut rng = Rng::new(1, 0);
+
+    for _ in 0..64 {
+        let sample = map.sample(&mut rng);
+        let (u, v) = EnvironmentMap::dir_to_uv(sample.dir);
+
+        assert!((0.25..0.375).contains(&u), "{u}");
+        assert!((0.25..0.5).contains(&v), "{v}");
+        assert!(crate::float_eq(sample.pdf / map.pdf(sample.dir), 1.0));
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use image::{ImageFormat, ImageResult, Rgb32FImage};
+
+use crate::{Color, Framebuffer};
+
+impl Framebuffer {
+    /// Saves the buffer in the format implied by the path's extension. `.hdr` (Radiance RGBE),
+    /// `.pfm` (portable float map) and `.exr` (OpenEXR) keep the full dynamic range. Anything
+    /// else is converted via `to_rgb_image`, so tone map first to keep highlights.
+    pub fn save(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        match extension(path).as_deref() {
+            Some("hdr") => {
+                let mut out = BufWriter::new(File::create(path)?);
+                write_hdr(self, &mut out)?;
+                Ok(out.flush()?)
+            }
+            Some("pfm") => {
+                let mut out = BufWriter::new(File::create(path)?);
+                write_pfm(self, &mut out)?;
+                Ok(out.flush()?)
+            }
+            Some("exr") => self
+                .to_rgb32f_image()
+                .save_with_format(path, ImageFormat::OpenExr),
+            _ => self.to_rgb_image().save(path),
+        }
+    }
+
+    /// Converts without any clamping or encoding
+    pub fn to_rgb32f_image(&self) -> Rgb32FImage {
+        let data = self.pixels().iter().flat_map(|c| [c.0, c.1, c.2]).collect();
+
+        Rgb32FImage::from_raw(self.width() as u32, self.height() as u32, data)
+            .expect("buffer size always matches its dimensions")
+    }
+}
+
+/// Whether `Framebuffer::save` keeps colors outside of [0, 1] when saving to `path`
+pub fn is_high_dynamic_range(path: impl AsRef<Path>) -> bool {
+    matches!(
+        extension(path.as_ref()).as_deref(),
+        Some("hdr" | "pfm" | "exr")
+    )
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+/// Packs a color into Radiance's shared-exponent format
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let max = color.0.max(color.1).max(color.2);
+    if max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // equivalent to C's frexp, max = mantissa * 2^exponent with mantissa in [0.5, 1)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / 2f32.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let scale = 256.0 / 2f32.powi(exponent);
+
+    let channel = |c: f32| (c.max(0.0) * scale).min(255.0) as u8;
+
+    [
+        channel(color.0),
+        channel(color.1),
+        channel(color.2),
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+/// Writes `data` as run-length encoded scanline data. Runs are only used when they save space,
+/// everything else is written as literal dumps.
+fn write_rle_channel(out: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+
+    let mut i = 0;
+    while i < data.len() {
+        // look for the next run worth encoding
+        let mut run_start = i;
+        let mut run_len = 0;
+        while run_start < data.len() {
+            run_len = 1;
+            while run_start + run_len < data.len()
+                && run_len < 127
+                && data[run_start + run_len] == data[run_start]
+            {
+                run_len += 1;
+            }
+            if run_len >= MIN_RUN {
+                break;
+            }
+            run_start += run_len;
+        }
+
+        // everything before the run goes out as literals
+        for chunk in data[i..run_start].chunks(128) {
+            out.write_all(&[chunk.len() as u8])?;
+            out.write_all(chunk)?;
+        }
+
+        if run_len >= MIN_RUN {
+            out.write_all(&[128 + run_len as u8, data[run_start]])?;
+            i = run_start + run_len;
+        } else {
+            i = run_start;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a Radiance `.hdr` image with RLE scanlines
+pub fn write_hdr(buffer: &Framebuffer, out: &mut impl Write) -> io::Result<()> {
+    let (width, height) = (buffer.width(), buffer.height());
+
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n"
+    )?;
+
+    for row in buffer.pixels().chunks(width.max(1)) {
+        let rgbe: Vec<[u8; 4]> = row.iter().map(|c| to_rgbe(*c)).collect();
+
+        // the RLE scheme can only describe scanlines within this range
+        if !(8..=0x7fff).contains(&width) {
+            for pixel in rgbe {
+                out.write_all(&pixel)?;
+            }
+            continue;
+        }
+
+        out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for channel in 0..4 {
+            let data: Vec<u8> = rgbe.iter().map(|p| p[channel]).collect();
+            write_rle_channel(out, &data)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a color portable float map, which stores rows from the bottom up
+pub fn write_pfm(buffer: &Framebuffer, out: &mut impl Write) -> io::Result<()> {
+    // a negative scale marks the data as little endian
+    write!(out, "PF\n{} {}\n-1.0\n", buffer.width(), buffer.height())?;
+
+    for row in buffer.pixels().chunks(buffer.width().max(1)).rev() {
+        for c in row {
+            out.write_all(&c.0.to_le_bytes())?;
+            out.write_all(&c.1.to_le_bytes())?;
+            out.write_all(&c.2.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn hdr_test_buffer() -> Framebuffer {
+    // wide enough to use RLE scanlines, with a run in the middle
+    let mut pixels = vec![Color(0.25, 0.5, 1.0); 12];
+    pixels[0] = Color(10.0, 0.0, 0.5);
+    pixels[11] = Color(0.001, 2.5, 100.0);
+
+    Framebuffer::from_pixels(12, 1, pixels)
+}
+
+#[test]
+pub fn test_export_hdr() {
+    let buffer = hdr_test_buffer();
+    let name = format!("raytrace_test_export_{}.hdr", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    buffer.save(&path).unwrap();
+
+    // `image::open` only gives back 8-bit data for .hdr files
+    let file = std::io::BufReader::new(File::open(&path).unwrap());
+    let loaded = image::codecs::hdr::HdrDecoder::new(file)
+        .unwrap()
+        .read_image_hdr()
+        .unwrap();
+    assert_eq!(loaded.len(), 12);
+
+    for (x, p) in loaded.iter().enumerate() {
+        let expected = buffer.get(x, 0);
+        // RGBE has 8 bits of mantissa shared across channels
+        let tolerance = expected.0.max(expected.1).max(expected.2) / 128.0;
+        assert!((p[0] - expected.0).abs() <= tolerance, "{x}: {p:?}");
+        assert!((p[1] - expected.1).abs() <= tolerance, "{x}: {p:?}");
+        assert!((p[2] - expected.2).abs() <= tolerance, "{x}: {p:?}");
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+pub fn test_export_exr() {
+    let buffer = hdr_test_buffer();
+    let name = format!("raytrace_test_export_{}.exr", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    buffer.save(&path).unwrap();
+
+    let loaded = image::open(&path).unwrap().into_rgb32f();
+    assert_eq!(loaded, buffer.to_rgb32f_image());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+pub fn test_export_pfm() {
+    let buffer = Framebuffer::from_pixels(1, 2, vec![Color(1.0, 2.0, 3.0), Color(4.0, 5.0, 6.0)]);
+    let mut out = Vec::new();
+    write_pfm(&buffer, &mut out).unwrap();
+
+    let header = b"PF\n1 2\n-1.0\n";
+    assert_eq!(&out[..header.len()], header);
+
+    // bottom row first
+    let first = f32::from_le_bytes(out[header.len()..header.len() + 4].try_into().unwrap());
+    assert_eq!(first, 4.0);
+    assert_eq!(out.len(), header.len() + (2 * 3 * 4));
+}
@@ -0,0 +1,130 @@
+use image::{Rgb, RgbImage};
+
+use crate::Color;
+
+/// A grid of unclamped, linear colors. Rendering writes into one of these so that samples can be
+/// accumulated and averaged without losing any energy, and converting to a displayable image is
+/// left to a separate step.
+///
+/// Coordinates are top-left relative, the same as `image`'s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::BLACK; width * height],
+        }
+    }
+
+    /// Stores pixels in row-major order, starting from the top left
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count must match framebuffer dimensions"
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[(y * self.width) + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[(y * self.width) + x] = color;
+    }
+
+    /// Adds `color` on top of whatever is already stored at (x, y)
+    pub fn add(&mut self, x: usize, y: usize, color: Color) {
+        let pixel = &mut self.pixels[(y * self.width) + x];
+        *pixel = *pixel + color;
+    }
+
+    /// Adds every pixel of `other` to the matching pixel of `self`
+    pub fn accumulate(&mut self, other: &Framebuffer) {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "framebuffers must be the same size to accumulate"
+        );
+
+        for (a, b) in self.pixels.iter_mut().zip(other.pixels.iter()) {
+            *a = *a + *b;
+        }
+    }
+
+    /// Returns a copy with every pixel multiplied by `factor`
+    pub fn scaled(&self, factor: f32) -> Framebuffer {
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|p| *p * factor).collect(),
+        }
+    }
+
+    /// Divides an accumulated buffer by the number of samples that went into each pixel
+    pub fn averaged(&self, samples: u32) -> Framebuffer {
+        if samples == 0 {
+            return self.clone();
+        }
+
+        self.scaled(1.0 / samples as f32)
+    }
+
+    /// Converts to 8-bit sRGB, clamping anything outside of [0, 1]. Tone map first to keep
+    /// highlights, see `Framebuffer::tone_mapped`.
+    pub fn to_rgb_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            Rgb(self.get(x as usize, y as usize).into())
+        })
+    }
+}
+
+#[test]
+pub fn test_framebuffer_accumulate() {
+    let mut total = Framebuffer::new(2, 1);
+    let mut sample = Framebuffer::new(2, 1);
+
+    sample.set(0, 0, Color(3.0, 0.0, 0.5));
+    sample.set(1, 0, Color(1.0, 1.0, 1.0));
+
+    for _ in 0..4 {
+        total.accumulate(&sample);
+    }
+    total.add(1, 0, Color(4.0, 0.0, 0.0));
+
+    let average = total.averaged(4);
+    // values above 1.0 survive until they're converted
+    assert_eq!(average.get(0, 0), Color(3.0, 0.0, 0.5));
+    assert_eq!(average.get(1, 0), Color(2.0, 1.0, 1.0));
+
+    let image = average.to_rgb_image();
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 188]);
+}
@@ -1,12 +1,38 @@
+pub mod animation;
+pub mod aov;
+pub mod cli;
+pub mod denoise;
+pub mod environment;
+pub mod export;
+pub mod framebuffer;
+pub mod media;
+pub mod noise;
+pub mod occlusion;
+pub mod palette;
+pub mod pathtrace;
+pub mod preview;
+pub mod progress;
+pub mod progressive;
+pub mod render;
+pub mod sampling;
 pub mod scene;
+pub mod scene_file;
+pub mod sequence;
+pub mod sky;
+pub mod tonemap;
 pub mod viewport;
+pub mod watch;
+pub mod yaml;
 
 pub mod primitives {
     pub mod color;
     pub mod matrix;
+    pub mod mat4;
     pub mod pos;
     pub mod vector;
     pub mod ray;
+    pub mod frame;
+    pub mod quat;
 
 
 
@@ -15,27 +41,25 @@ pub mod primitives {
 pub mod objects {
     pub mod sphere;
     pub mod material;
+    pub mod normal_map;
+    pub mod texture;
+    pub mod uv;
 
     pub use sphere::Sphere;
 }
 
 use std::sync::Arc;
 
-pub use primitives::{color::Color, matrix::Matrix, pos::Pos3, vector::Vec3, ray::Ray};
+pub use primitives::{color::Color, matrix::Matrix, mat4::Mat4, pos::Pos3, vector::Vec3, ray::Ray, quat::Quat};
 pub use viewport:: Viewport;
+pub use framebuffer::Framebuffer;
 pub use scene::Scene;
-
-use image::Rgb;
+pub use render::Renderer;
 
 #[macro_export]
 macro_rules! identity_matrix {
     () => {
-        Matrix::from_vec(vec![
-            vec![1.0, 0.0, 0.0, 0.0],
-            vec![0.0, 1.0, 0.0, 0.0],
-            vec![0.0, 0.0, 1.0, 0.0],
-            vec![0.0, 0.0, 0.0, 1.0],
-        ])
+        $crate::Mat4::IDENTITY
     };
 }
 
@@ -49,6 +73,16 @@ pub fn topleft_rel(width: usize, height: usize, x: isize, y: isize) -> (usize, u
     )
 }
 
+/// The inverse of `topleft_rel`
+///
+/// e.g. screen size of 1920x1080, (0, 0) -> (-960, 539)
+pub fn center_rel(width: usize, height: usize, x: usize, y: usize) -> (isize, isize) {
+    (
+        x as isize - (width as isize / 2),
+        (height as isize / 2) - y as isize - 1,
+    )
+}
+
 pub fn float_eq(a: f32, b: f32) -> bool {
     (a - b).abs() <= 0.001
 }
@@ -68,6 +102,23 @@ impl PointLight {
     }
 }
 
+/// A light infinitely far away, such as the sun, that arrives from the same direction everywhere
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    /// Points from the scene towards the light
+    direction: Vec3,
+    intensity: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, intensity: Color) -> Self {
+        Self {
+            direction: direction.to_normalized(),
+            intensity,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum Surface {
     Matte,
@@ -1,65 +1,224 @@
-use std::{sync::Arc, sync::Mutex, time::Instant};
+use std::{
+    env,
+    error::Error,
+    io::{self, Read},
+    path::Path,
+    process::ExitCode,
+    thread,
+    time::{Duration, Instant},
+};
 
-use image::{Rgb, RgbImage};
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use raytrace as rt;
 use rt::{
-    identity_matrix,
-    objects::{material::Material, Sphere},
-    topleft_rel, Color, Matrix, PointLight, Pos3, Scene, Surface, Vec3, Viewport,
+    cli::{self, Command, Options},
+    preview::{self as window, MinifbDisplay, OrbitControls},
+    progress::{CancelToken, Progress},
+    scene_file::SceneFile,
+    sequence::{self, SequenceFormat},
+    watch::{Reload, SceneWatcher},
+    Framebuffer, Renderer,
 };
 
-const WIDTH: usize = 1000;
-const HEIGHT: usize = 1000;
+/// How often watch mode checks the scene file for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
 
-const LOW_WIDTH: isize = -(WIDTH as isize / 2);
-const HIGH_WIDTH: isize = WIDTH as isize / 2;
-const LOW_HEIGHT: isize = -(HEIGHT as isize / 2);
-const HIGH_HEIGHT: isize = HEIGHT as isize / 2;
+fn main() -> ExitCode {
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{}", cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
 
-const BACKGROUND_COLOR: [u8; 3] = [0, 0, 0];
+    let result = if let Err(e) = set_threads(&options) {
+        Err(e)
+    } else if options.watch {
+        watch(&options)
+    } else if options.preview {
+        preview(&options)
+    } else {
+        run(&options)
+    };
 
-fn main() {
-    //     let canvas = Canvas::new(WIDTH, HEIGHT);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let identity = identity_matrix!();
+/// Sizes rayon's global pool, which every render runs on
+fn set_threads(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = options.threads {
+        pool = pool.num_threads(threads);
+    }
+
+    Ok(pool.build_global()?)
+}
 
-    let spheres = vec![Arc::new(Sphere::new(
-        identity,
-        Material::new(Color(1.0, 0.2, 1.0), 0.1, 0.9, 0.9, 200.0),
-    ))];
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut file = load(options)?;
+    file.shutter = options.shutter(&file);
 
-    let scene = Scene {
-        spheres,
-        lights: vec![PointLight::new(
-            Pos3::new(-10.0, 10.0, -10.0),
-            Color(1.0, 1.0, 1.0),
-        )],
-        bg_color: BACKGROUND_COLOR,
+    let Some(frames) = options.frames.clone() else {
+        let image = render(options, file.at(0.0), &options.output)?;
+        return save(options, &[image], &options.output);
     };
 
-    let image = Mutex::new(RgbImage::new(WIDTH as u32, HEIGHT as u32));
+    // a GIF or APNG holds every frame, anything else is saved a frame at a time
+    let animated = SequenceFormat::from_path(&options.output).is_some();
+    let mut images = Vec::new();
 
-    let viewport = Viewport::new(Pos3::new(0.0, 0.0, -5.0), 1.0, 1.0);
+    let count = frames.len();
+    for (i, frame) in frames.enumerate() {
+        eprintln!("Frame {frame} ({} of {count})", i + 1);
+        let path = options.frame_path(frame);
+        let image = render(options, file.at(options.frame_time(frame)), &path)?;
+        if animated {
+            images.push(image);
+        } else {
+            save(options, &[image], &path)?;
+        }
+    }
 
-    let now = Instant::now();
+    if animated {
+        save(options, &images, &options.output)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the scene file, or the scene from stdin when it's given as `-`
+fn load(options: &Options) -> Result<SceneFile, Box<dyn Error>> {
+    if !options.reads_stdin() {
+        return SceneFile::open(&options.scene)
+            .map_err(|e| format!("couldn't load {}: {e}", options.scene.display()).into());
+    }
 
-    // pretty lazy way to do it, the mutex will slow it down a lot
-    (LOW_WIDTH..HIGH_WIDTH - 1).into_par_iter().for_each(|x| {
-        for y in LOW_HEIGHT..HIGH_HEIGHT - 1 {
-            let d = viewport.ray_from_coord(x, y, WIDTH, HEIGHT);
-            let color = scene.trace_ray(d, 1.0, f32::MAX, 3);
-            let (rx, ry) = topleft_rel(WIDTH, HEIGHT, x, y);
-            image
-                .lock()
-                .unwrap()
-                .put_pixel(rx as u32, ry as u32, Rgb(color));
+    let mut source = String::new();
+    io::stdin()
+        .read_to_string(&mut source)
+        .map_err(|e| format!("couldn't read the scene from stdin: {e}"))?;
+    SceneFile::parse(&source).map_err(|e| format!("couldn't load the scene from stdin: {e}").into())
+}
+
+/// Saves a single image, or every frame of an animation when `output` is a GIF or APNG. Both are
+/// tone mapped unless the format keeps the full dynamic range.
+fn save(options: &Options, images: &[Framebuffer], output: &Path) -> Result<(), Box<dyn Error>> {
+    let result: Result<(), Box<dyn Error>> = match (SequenceFormat::from_path(output), images) {
+        (None, [image]) => image
+            .save_tone_mapped(output, &options.tonemap)
+            .map_err(Into::into),
+        _ => {
+            let frames: Vec<Framebuffer> = images
+                .iter()
+                .map(|image| image.tone_mapped(&options.tonemap))
+                .collect();
+            sequence::save_sequence(&frames, output, &options.sequence_settings())
+                .map_err(Into::into)
         }
-    });
+    };
+
+    result.map_err(|e| format!("couldn't save {}: {e}", output.display()).into())
+}
+
+/// Renders `file`, saving any AOVs next to `output`
+fn render(
+    options: &Options,
+    file: SceneFile,
+    output: &Path,
+) -> Result<Framebuffer, Box<dyn Error>> {
+    let settings = options.render_settings(&file);
+    let renderer = Renderer::new(file.scene, file.camera, settings);
+
+    let now = Instant::now();
+
+    let on_progress = |p: &Progress| {
+        eprint!("\r{:>3.0}% ETA {:.1?}", p.fraction() * 100.0, p.eta);
+    };
+    let cancel = CancelToken::new();
+    let (image, aovs) = if options.aovs.is_empty() {
+        (renderer.render_with_progress(on_progress, &cancel)?, None)
+    } else {
+        let (image, aovs) = renderer.render_with_aovs(&options.aovs, on_progress, &cancel)?;
+        (image, Some(aovs))
+    };
+    eprintln!();
+
+    eprintln!("Rendered in {:?}", now.elapsed());
+
+    if let Some(aovs) = aovs {
+        let path = output.with_extension("exr");
+        aovs.save(&path)
+            .map_err(|e| format!("couldn't save AOVs next to {}: {e}", path.display()))?;
+    }
 
-    let dur = now.elapsed();
+    Ok(image)
+}
+
+/// Opens a window showing the render as it comes in, saving whatever was last finished when it's
+/// closed
+fn preview(options: &Options) -> Result<(), Box<dyn Error>> {
+    let file = load(options)?;
+    let settings = options.render_settings(&file);
+    let mut renderer = Renderer::new(file.scene, file.camera, settings);
+
+    let mut display = MinifbDisplay::new("raytrace", settings.width, settings.height)
+        .map_err(|e| format!("couldn't open a window: {e}"))?;
+    let mut controls = OrbitControls::around_view(&renderer.camera);
 
-    println!("Time to trace rays: {dur:?}");
+    let image = window::preview(&mut display, &mut renderer, &mut controls, &options.tonemap);
+    match image {
+        Some(image) => save(options, &[image], &options.output)?,
+        None => eprintln!("Closed before the render finished, nothing was saved"),
+    }
+
+    Ok(())
+}
+
+/// Renders a preview every time the scene file changes. Errors in the file are printed and the
+/// last scene that loaded stays on screen, so a half-finished edit doesn't end the session.
+fn watch(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut watcher = SceneWatcher::new(&options.scene);
+    eprintln!("Watching {}, press Ctrl-C to stop", options.scene.display());
+
+    loop {
+        match watcher.poll() {
+            Some(Reload::Loaded) => {
+                let file = watcher.scene().expect("a scene was just loaded");
+                let settings = options.preview_settings(file);
+                let renderer = Renderer::new(file.scene.clone(), file.camera.clone(), settings);
+
+                let now = Instant::now();
+                let image = renderer.render();
+                match image.save_tone_mapped(&options.output, &options.tonemap) {
+                    Ok(()) => eprintln!(
+                        "Rendered {}x{} preview to {} in {:?}",
+                        settings.width,
+                        settings.height,
+                        options.output.display(),
+                        now.elapsed()
+                    ),
+                    Err(e) => eprintln!("error: couldn't save {}: {e}", options.output.display()),
+                }
+            }
+            Some(Reload::Failed(e)) => {
+                eprintln!("error: couldn't load {}: {e}", watcher.path().display());
+                if watcher.scene().is_some() {
+                    eprintln!("Keeping the last scene that loaded");
+                }
+            }
+            None => {}
+        }
 
-    image.lock().unwrap().save("./test.png").unwrap();
+        thread::sleep(POLL_INTERVAL);
+    }
 }
@@ -0,0 +1,470 @@
+use std::f32::consts::PI;
+
+use crate::{
+    noise::fbm, primitives::frame::TangentFrame, sampling::Rng, scene::SURFACE_EPSILON, Color,
+    Pos3, Ray, Scene, Vec3,
+};
+
+#[cfg(test)]
+use crate::{objects::material::Material, objects::Sphere, Mat4};
+#[cfg(test)]
+use std::sync::Arc;
+
+/// Distance between density lookups when estimating transmittance through a varying medium
+const MARCH_STEP: f32 = 0.05;
+/// Caps the number of density lookups along a single segment
+const MAX_MARCH_STEPS: usize = 512;
+
+/// How a medium's coefficients vary over space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Density {
+    /// The coefficients apply unchanged everywhere
+    Homogeneous,
+    /// The coefficients are scaled by fractal noise in [0, 1], sampled in world space
+    Noise { scale: f32, octaves: u32 },
+}
+
+/// Participating media such as fog, smoke or murky liquid. Coefficients are per unit of
+/// distance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Medium {
+    /// How much light is lost to the medium
+    pub absorption: Color,
+    /// How much light is redirected by the medium
+    pub scattering: Color,
+    pub density: Density,
+    /// Henyey-Greenstein asymmetry, from -1.0 (backwards) through 0.0 (evenly in all directions)
+    /// to 1.0 (forwards)
+    pub anisotropy: f32,
+}
+
+impl Medium {
+    pub fn homogeneous(absorption: Color, scattering: Color) -> Self {
+        Self {
+            absorption,
+            scattering,
+            density: Density::Homogeneous,
+            anisotropy: 0.0,
+        }
+    }
+
+    pub fn with_density(mut self, density: Density) -> Self {
+        self.density = density;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    /// Total rate at which light is removed from a ray, before density is applied
+    pub fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+
+    /// Fraction of extinction that is scattering rather than absorption
+    pub fn albedo(&self) -> Color {
+        let e = self.extinction();
+        let ratio = |s: f32, e: f32| if e > 0.0 { s / e } else { 0.0 };
+
+        Color(
+            ratio(self.scattering.0, e.0),
+            ratio(self.scattering.1, e.1),
+            ratio(self.scattering.2, e.2),
+        )
+    }
+
+    /// Density multiplier at a point, in [0, 1]
+    pub fn density_at(&self, point: Pos3) -> f32 {
+        match self.density {
+            Density::Homogeneous => 1.0,
+            Density::Noise { scale, octaves } => {
+                let p = Pos3::new(point.x * scale, point.y * scale, point.z * scale);
+                ((fbm(p, octaves) + 1.0) * 0.5).clamp(0.0, 1.0)
+            }
+        }
+    }
+
+    /// Fraction of light that makes it through the medium along `ray` between `t0` and `t1`, via
+    /// the Beer-Lambert law. `ray.dir` doesn't need to be normalized.
+    pub fn transmittance(&self, ray: &Ray, t0: f32, t1: f32) -> Color {
+        if t1 <= t0 {
+            return Color::WHITE;
+        }
+
+        let dist = (t1 - t0) * ray.dir.magnitude();
+
+        let optical_depth = match self.density {
+            Density::Homogeneous => dist,
+            Density::Noise { .. } => {
+                let steps = ((dist / MARCH_STEP).ceil() as usize).clamp(1, MAX_MARCH_STEPS);
+                let dt = (t1 - t0) / steps as f32;
+
+                // midpoint rule
+                let total: f32 = (0..steps)
+                    .map(|i| self.density_at(ray.position(t0 + ((i as f32 + 0.5) * dt))))
+                    .sum();
+
+                total * (dist / steps as f32)
+            }
+        };
+
+        let e = self.extinction();
+        Color(
+            (-e.0 * optical_depth).exp(),
+            (-e.1 * optical_depth).exp(),
+            (-e.2 * optical_depth).exp(),
+        )
+    }
+
+    /// Samples the distance to the next scattering or absorption event along `ray` between `t0`
+    /// and `t1`, or None if the ray passes through, see `Scene::sample_media`. `ray.dir` must be
+    /// normalized.
+    pub fn sample_interaction(
+        &self,
+        ray: &Ray,
+        t0: f32,
+        t1: f32,
+        rng: &mut Rng,
+    ) -> (Option<f32>, Color) {
+        let (event, weight) = delta_track(&[(self, t0, t1)], ray, rng);
+        (event.map(|(t, _)| t), weight)
+    }
+
+    /// Henyey-Greenstein phase function for light travelling along `dir_in` and leaving along
+    /// `dir_out`. Both are expected to be normalized.
+    pub fn phase(&self, dir_in: Vec3, dir_out: Vec3) -> f32 {
+        henyey_greenstein(dir_in * dir_out, self.anisotropy)
+    }
+
+    /// Picks a new direction for light travelling along `dir_in`, in proportion to `phase`
+    pub fn sample_phase(&self, dir_in: Vec3, rng: &mut Rng) -> Vec3 {
+        let g = self.anisotropy;
+        let u = rng.next_f32();
+
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - (2.0 * u)
+        } else {
+            let s = (1.0 - (g * g)) / (1.0 - g + (2.0 * g * u));
+            ((1.0 + (g * g) - (s * s)) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+
+        let sin_theta = (1.0 - (cos_theta * cos_theta)).max(0.0).sqrt();
+        let (sin_p, cos_p) = (2.0 * PI * rng.next_f32()).sin_cos();
+
+        TangentFrame::from_normal(dir_in).to_world(Vec3::new(
+            sin_theta * cos_p,
+            sin_theta * sin_p,
+            cos_theta,
+        ))
+    }
+}
+
+fn average(c: Color) -> f32 {
+    (c.0 + c.1 + c.2) / 3.0
+}
+
+/// Delta tracking through overlapping media, returning the first real collision and the index of
+/// the medium it's in. Tentative collisions are spaced by the sum of each medium's largest
+/// extinction channel, which bounds the real extinction everywhere, and are kept or turned into
+/// null collisions by the average channel. The returned weight corrects every channel for that
+/// choice, so colored media stay unbiased, and is needed whether or not there was a collision.
+fn delta_track(
+    segments: &[(&Medium, f32, f32)],
+    ray: &Ray,
+    rng: &mut Rng,
+) -> (Option<(f32, usize)>, Color) {
+    let majorant: f32 = segments
+        .iter()
+        .map(|(medium, _, _)| {
+            let e = medium.extinction();
+            e.0.max(e.1).max(e.2)
+        })
+        .sum();
+    let start = segments.iter().map(|s| s.1).fold(f32::INFINITY, f32::min);
+    let end = segments
+        .iter()
+        .map(|s| s.2)
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    let mut weight = Color::WHITE;
+    if majorant <= 0.0 {
+        return (None, weight);
+    }
+
+    let mut t = start;
+    loop {
+        t -= (1.0 - rng.next_f32()).ln() / majorant;
+        if t >= end {
+            return (None, weight);
+        }
+
+        let point = ray.position(t);
+        let extinctions: Vec<(usize, Color)> = segments
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, t0, t1))| t >= *t0 && t < *t1)
+            .map(|(i, (medium, _, _))| (i, medium.extinction() * medium.density_at(point)))
+            .collect();
+        let extinction = extinctions
+            .iter()
+            .fold(Color::BLACK, |acc, (_, e)| acc + *e);
+
+        let mut pick = rng.next_f32() * majorant;
+        if pick < average(extinction) {
+            // the medium is picked in proportion to its share of the average extinction
+            let last = extinctions.len() - 1;
+            for (n, (i, e)) in extinctions.iter().enumerate() {
+                if pick < average(*e) || n == last {
+                    return (Some((t, *i)), weight * *e * (1.0 / average(*e)));
+                }
+                pick -= average(*e);
+            }
+        }
+
+        let null = Color(majorant, majorant, majorant) - extinction;
+        if average(null) > 0.0 {
+            weight = weight * null * (1.0 / average(null));
+        }
+    }
+}
+
+/// `cos_theta` is the angle between the incoming and outgoing directions of travel
+pub fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let denom = 1.0 + (g * g) - (2.0 * g * cos_theta);
+    (1.0 - (g * g)) / (4.0 * PI * denom * denom.sqrt())
+}
+
+/// A medium filling the scene out to `extent` from `center`, usually the camera's position.
+/// It's measured from a fixed point rather than from each ray's origin, so bounces and shadow
+/// rays only pass through the fog that's actually around them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fog {
+    pub medium: Medium,
+    pub center: Pos3,
+    /// Rays that escape the scene only pass through this much fog, so the environment is still
+    /// visible behind it
+    pub extent: f32,
+}
+
+impl Fog {
+    pub fn new(medium: Medium, center: Pos3, extent: f32) -> Self {
+        Self {
+            medium,
+            center,
+            extent,
+        }
+    }
+
+    /// Where `ray` enters and leaves the fog, if it passes through it at all
+    fn span(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let to_origin = ray.origin - self.center;
+        let a = ray.dir * ray.dir;
+        let b = 2.0 * (ray.dir * to_origin);
+        let c = (to_origin * to_origin) - (self.extent * self.extent);
+
+        let discriminant = (b * b) - (4.0 * a * c);
+        if a == 0.0 || discriminant < 0.0 {
+            return None;
+        }
+
+        let root = discriminant.sqrt();
+        Some(((-b - root) / (2.0 * a), (-b + root) / (2.0 * a)))
+    }
+}
+
+impl Scene {
+    /// Returns every stretch of `ray` between `t_min` and `t_max` that passes through a medium,
+    /// including the scene's fog
+    pub fn media_segments(&self, ray: &Ray, t_min: f32, t_max: f32) -> Vec<(&Medium, f32, f32)> {
+        let mut segments = Vec::new();
+
+        if let Some(fog) = &self.fog {
+            if let Some((near, far)) = fog.span(ray) {
+                let t0 = near.max(t_min);
+                let t1 = far.min(t_max);
+                if t1 > t0 {
+                    segments.push((&fog.medium, t0, t1));
+                }
+            }
+        }
+
+        for sphere in self.spheres.iter() {
+            let Some(medium) = &sphere.medium else {
+                continue;
+            };
+
+            let intersects = ray.sphere_intersect(sphere);
+            let (near, far) = (intersects[0].t, intersects[1].t);
+            if near.is_nan() || far.is_nan() {
+                continue;
+            }
+
+            let t0 = near.max(t_min);
+            let t1 = far.min(t_max);
+            if t1 > t0 {
+                segments.push((medium.as_ref(), t0, t1));
+            }
+        }
+
+        segments
+    }
+
+    /// Fraction of light that makes it through every medium along `ray` between `t_min` and
+    /// `t_max`. Surfaces are ignored.
+    pub fn media_transmittance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Color {
+        self.media_segments(ray, t_min, t_max)
+            .into_iter()
+            .fold(Color::WHITE, |acc, (medium, t0, t1)| {
+                acc * medium.transmittance(ray, t0, t1)
+            })
+    }
+
+    /// Fraction of light that makes it from the ray's origin to `t_max`, accounting for both
+    /// surfaces and media. Used for shadow rays.
+    pub fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+        if self.is_occluded(ray, t_max) {
+            return Color::BLACK;
+        }
+
+        self.media_transmittance(ray, SURFACE_EPSILON, t_max)
+    }
+
+    /// Samples where `ray` first interacts with a medium before reaching `t_max`, returning the
+    /// distance and the medium involved. The weight has to be applied to the path's throughput
+    /// either way, since it's what keeps colored extinction unbiased. `ray.dir` must be
+    /// normalized.
+    pub fn sample_media(
+        &self,
+        ray: &Ray,
+        t_max: f32,
+        rng: &mut Rng,
+    ) -> (Option<(f32, &Medium)>, Color) {
+        let segments = self.media_segments(ray, 0.0, t_max);
+        let (event, weight) = delta_track(&segments, ray, rng);
+        (event.map(|(t, i)| (t, segments[i].0)), weight)
+    }
+}
+
+#[test]
+pub fn test_medium_transmittance() {
+    let ray = Ray::new(Pos3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0));
+    let medium = Medium::homogeneous(Color(0.5, 0.0, 0.0), Color(0.0, 0.5, 0.0));
+
+    // t is scaled by the unnormalized direction, so this covers a distance of 2
+    let tr = medium.transmittance(&ray, 0.0, 1.0);
+    assert_eq!(tr, Color((-1.0f32).exp(), (-1.0f32).exp(), 1.0));
+
+    // a density of 0.5 everywhere should match the homogeneous case
+    let noisy = medium.clone().with_density(Density::Noise {
+        scale: 0.0,
+        octaves: 1,
+    });
+    assert_eq!(noisy.density_at(Pos3::new(0.4, 0.3, 0.2)), 0.5);
+    assert_eq!(
+        noisy.transmittance(&ray, 0.0, 1.0),
+        Color((-0.5f32).exp(), (-0.5f32).exp(), 1.0)
+    );
+}
+
+#[test]
+pub fn test_delta_tracking_colored() {
+    let mut rng = Rng::new(7, 0);
+    let ray = Ray::new(Pos3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+    let medium = Medium::homogeneous(Color(0.1, 0.4, 0.7), Color(0.3, 0.4, 0.5));
+    let noisy = medium.clone().with_density(Density::Noise {
+        scale: 0.7,
+        octaves: 2,
+    });
+
+    // the weights of the rays that pass through estimate the transmittance of every channel
+    let n = 40000;
+    let mut estimate = |sample: &mut dyn FnMut(&mut Rng) -> (bool, Color)| {
+        (0..n).fold(Color::BLACK, |acc, _| match sample(&mut rng) {
+            (false, weight) => acc + (weight * (1.0 / n as f32)),
+            (true, _) => acc,
+        })
+    };
+    let close = |a: Color, b: Color| {
+        [(a.0, b.0), (a.1, b.1), (a.2, b.2)]
+            .iter()
+            .all(|(a, b)| (a - b).abs() < 0.02)
+    };
+
+    let passed = estimate(&mut |rng| {
+        let (t, weight) = medium.sample_interaction(&ray, 0.0, 1.5, rng);
+        (t.is_some(), weight)
+    });
+    let expected = Color((-0.6f32).exp(), (-1.2f32).exp(), (-1.8f32).exp());
+    assert!(close(passed, expected), "{passed:?} vs {expected:?}");
+
+    let passed = estimate(&mut |rng| {
+        let (t, weight) = noisy.sample_interaction(&ray, 0.0, 1.5, rng);
+        (t.is_some(), weight)
+    });
+    let expected = noisy.transmittance(&ray, 0.0, 1.5);
+    assert!(close(passed, expected), "{passed:?} vs {expected:?}");
+
+    // overlapping media are tracked together
+    let scene = Scene {
+        spheres: vec![Arc::new(
+            Sphere::new(Mat4::translation(0.0, 0.0, 1.0), Material::default())
+                .with_medium(Arc::new(noisy)),
+        )],
+        fog: Some(Fog::new(medium, Pos3::new(0.0, 0.0, 0.0), 1.5)),
+        ..Default::default()
+    };
+    let passed = estimate(&mut |rng| {
+        let (event, weight) = scene.sample_media(&ray, 2.5, rng);
+        (event.is_some(), weight)
+    });
+    let expected = scene.media_transmittance(&ray, 0.0, 2.5);
+    assert!(close(passed, expected), "{passed:?} vs {expected:?}");
+}
+
+#[test]
+pub fn test_henyey_greenstein_normalized() {
+    // integrate over the sphere, which only depends on cos_theta
+    for g in [-0.7, 0.0, 0.3, 0.9] {
+        let n = 20000;
+        let total: f32 = (0..n)
+            .map(|i| {
+                let cos_theta = -1.0 + (2.0 * (i as f32 + 0.5) / n as f32);
+                henyey_greenstein(cos_theta, g) * 2.0 * PI * (2.0 / n as f32)
+            })
+            .sum();
+
+        assert!((total - 1.0).abs() < 0.01, "{g}: {total}");
+    }
+}
+
+#[test]
+pub fn test_scene_media() {
+    let medium = Medium::homogeneous(Color(1.0, 1.0, 1.0), Color::BLACK);
+    let scene = Scene {
+        spheres: vec![Arc::new(
+            Sphere::new(Mat4::scaling(2.0, 2.0, 2.0), Material::default())
+                .with_medium(Arc::new(medium.clone())),
+        )],
+        fog: Some(Fog::new(medium, Pos3::new(0.0, 0.0, -5.0), 1.0)),
+        ..Default::default()
+    };
+
+    let ray = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+    // volumes don't block rays like surfaces do
+    assert!(!scene.is_occluded(&ray, f32::MAX));
+
+    // 1 unit of fog, then the 4 unit wide sphere
+    let segments = scene.media_segments(&ray, 0.0, f32::MAX);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(
+        scene.transmittance(&ray, f32::MAX),
+        Color::WHITE * (-5.0f32).exp()
+    );
+
+    // a ray starting away from the camera doesn't pick up the fog around it again
+    let bounce = Ray::new(Pos3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, 1.0));
+    assert!(scene.media_segments(&bounce, 0.0, f32::MAX).is_empty());
+}
@@ -0,0 +1,115 @@
+use crate::Pos3;
+
+/// Gradient directions used by Perlin's improved noise: the midpoints of a cube's edges
+const GRADIENTS: [(f32, f32, f32); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+/// Scrambles a lattice coordinate into a well-distributed integer. Stands in for Perlin's
+/// permutation table, so there's nothing to initialize.
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6b343)
+        ^ (y as u32).wrapping_mul(0xd8163841)
+        ^ (z as u32).wrapping_mul(0xcb1ab31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846ca68b);
+    h ^ (h >> 16)
+}
+
+fn gradient_dot(x: i32, y: i32, z: i32, dx: f32, dy: f32, dz: f32) -> f32 {
+    let (gx, gy, gz) = GRADIENTS[(hash(x, y, z) % 12) as usize];
+    (gx * dx) + (gy * dy) + (gz * dz)
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * ((t * ((t * 6.0) - 15.0)) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + ((b - a) * t)
+}
+
+/// Perlin's improved gradient noise. Smooth, roughly in [-1, 1], and 0.0 at every integer
+/// lattice point.
+pub fn perlin(point: Pos3) -> f32 {
+    let (fx, fy, fz) = (point.x.floor(), point.y.floor(), point.z.floor());
+    let (x, y, z) = (fx as i32, fy as i32, fz as i32);
+    let (dx, dy, dz) = (point.x - fx, point.y - fy, point.z - fz);
+    let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+
+    let corner = |ox: i32, oy: i32, oz: i32| {
+        gradient_dot(
+            x + ox,
+            y + oy,
+            z + oz,
+            dx - ox as f32,
+            dy - oy as f32,
+            dz - oz as f32,
+        )
+    };
+
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    )
+}
+
+/// Fractal Brownian motion: sums `octaves` layers of noise, each at double the frequency and half
+/// the amplitude of the last. Normalized to stay roughly in [-1, 1].
+pub fn fbm(point: Pos3, octaves: u32) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut norm = 0.0;
+
+    for _ in 0..octaves.max(1) {
+        let p = Pos3::new(
+            point.x * frequency,
+            point.y * frequency,
+            point.z * frequency,
+        );
+        total += perlin(p) * amplitude;
+        norm += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    total / norm
+}
+
+#[test]
+pub fn test_perlin() {
+    assert_eq!(perlin(Pos3::new(3.0, -2.0, 7.0)), 0.0);
+
+    let a = perlin(Pos3::new(0.3, 0.6, 0.2));
+    let b = perlin(Pos3::new(0.3001, 0.6, 0.2));
+    assert!((a - b).abs() < 0.01);
+    assert_eq!(a, perlin(Pos3::new(0.3, 0.6, 0.2)));
+
+    for i in 0..1000 {
+        let p = Pos3::new(i as f32 * 0.37, i as f32 * 0.11, i as f32 * -0.23);
+        assert!((-1.0..=1.0).contains(&fbm(p, 4)));
+    }
+}
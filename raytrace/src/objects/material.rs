@@ -1,4 +1,11 @@
-use crate::Color;
+use std::f32::consts::PI;
+
+use crate::{
+    objects::{normal_map::NormalMap, texture::Texture},
+    primitives::frame::TangentFrame,
+    sampling::{cosine_hemisphere, Rng},
+    Color, Pos3, Vec3,
+};
 
 #[derive(Debug, Clone)]
 pub struct Material {
@@ -7,6 +14,12 @@ pub struct Material {
     pub diffuse: f32,
     pub specular: f32,
     pub shine: f32,
+    /// Tinted by `color` when present
+    pub texture: Option<Texture>,
+    pub normal_map: Option<NormalMap>,
+    /// Written to the material ID AOV. Clones keep it, so spheres sharing a material can be told
+    /// apart from ones that only happen to look the same.
+    pub id: Option<usize>,
 }
 
 impl Default for Material {
@@ -17,12 +30,108 @@ impl Default for Material {
             diffuse: 0.9,
             specular: 0.9,
             shine: 200.0,
+            texture: None,
+            normal_map: None,
+            id: None,
         }
     }
 }
 
 impl Material {
     pub fn new(color: Color, ambient: f32, diffuse: f32, specular: f32, shine: f32) -> Self {
-        Self { color, ambient, diffuse, specular, shine }
+        Self {
+            color,
+            ambient,
+            diffuse,
+            specular,
+            shine,
+            texture: None,
+            normal_map: None,
+            id: None,
+        }
+    }
+
+    pub fn with_texture(mut self, texture: Texture) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Self {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    pub fn with_id(mut self, id: usize) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Returns the surface color at a point in object space
+    pub fn color_at(&self, object_point: Pos3) -> Color {
+        match &self.texture {
+            Some(texture) => self.color * texture.color_at(object_point),
+            None => self.color,
+        }
+    }
+
+    /// Chance of sampling the specular lobe rather than the diffuse one
+    fn specular_chance(&self) -> f32 {
+        let diffuse = self.diffuse * self.color.luminance();
+        if diffuse + self.specular <= 0.0 {
+            0.0
+        } else {
+            self.specular / (diffuse + self.specular)
+        }
+    }
+
+    /// Energy-conserving interpretation of the Phong model, with a Lambertian diffuse lobe and a
+    /// normalized specular lobe. `wo` points towards the viewer and `wi` towards the incoming
+    /// light. The `ambient` term is ignored, since indirect light is traced instead.
+    pub fn bsdf(&self, normal: Vec3, wo: Vec3, wi: Vec3) -> Color {
+        if wi * normal <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let diffuse = self.color * (self.diffuse / PI);
+
+        let reflected = (-wo).reflect(normal);
+        let cos_alpha = (reflected * wi).max(0.0);
+        let normalization = (self.shine + 2.0) / (2.0 * PI);
+        let specular = Color::WHITE * (self.specular * normalization * cos_alpha.powf(self.shine));
+
+        diffuse + specular
+    }
+
+    /// The pdf that `sample_bsdf` would have returned `wi`, with respect to solid angle
+    pub fn bsdf_pdf(&self, normal: Vec3, wo: Vec3, wi: Vec3) -> f32 {
+        let cos_theta = wi * normal;
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let reflected = (-wo).reflect(normal);
+        let cos_alpha = (reflected * wi).max(0.0);
+        let specular_pdf = (self.shine + 1.0) / (2.0 * PI) * cos_alpha.powf(self.shine);
+
+        let chance = self.specular_chance();
+        (chance * specular_pdf) + ((1.0 - chance) * cos_theta / PI)
+    }
+
+    /// Picks an incoming light direction in proportion to the BSDF. Returns `None` if the
+    /// direction ends up below the surface.
+    pub fn sample_bsdf(&self, normal: Vec3, wo: Vec3, rng: &mut Rng) -> Option<Vec3> {
+        let wi = if rng.next_f32() < self.specular_chance() {
+            let reflected = (-wo).reflect(normal);
+            let cos_alpha = rng.next_f32().powf(1.0 / (self.shine + 1.0));
+            let sin_alpha = (1.0 - (cos_alpha * cos_alpha)).max(0.0).sqrt();
+            let (sin_p, cos_p) = (2.0 * PI * rng.next_f32()).sin_cos();
+
+            TangentFrame::from_normal(reflected)
+                .to_world(Vec3::new(sin_alpha * cos_p, sin_alpha * sin_p, cos_alpha))
+        } else {
+            TangentFrame::from_normal(normal).to_world(cosine_hemisphere(rng))
+        };
+
+        (wi * normal > 0.0).then_some(wi)
     }
 }
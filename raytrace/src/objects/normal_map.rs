@@ -0,0 +1,132 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    objects::{texture::ImageTexture, uv::UvMap},
+    primitives::frame::TangentFrame,
+    Mat4, Pos3, Vec3,
+};
+
+/// A height field defined over object space
+pub type BumpFn = Arc<dyn Fn(Pos3) -> f32 + Send + Sync>;
+
+/// Step size used to estimate the slope of a `BumpFn`
+const BUMP_EPSILON: f32 = 0.0005;
+
+/// Perturbs the geometric normal of a surface without changing its shape
+#[derive(Clone)]
+pub enum NormalMap {
+    /// Tangent-space normals, with each channel mapped from [0, 1] to [-1, 1]. `strength` scales
+    /// the tangential part of the normal, where 0.0 leaves the surface flat. The image should be
+    /// loaded with `ImageTexture::open_linear`.
+    Image {
+        image: Arc<ImageTexture>,
+        mapping: UvMap,
+        strength: f32,
+    },
+    /// The normal is tilted against the slope of `height`, scaled by `scale`
+    Bump { height: BumpFn, scale: f32 },
+}
+
+impl fmt::Debug for NormalMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Image {
+                image,
+                mapping,
+                strength,
+            } => f
+                .debug_struct("Image")
+                .field("image", image)
+                .field("mapping", mapping)
+                .field("strength", strength)
+                .finish(),
+            Self::Bump { scale, .. } => f
+                .debug_struct("Bump")
+                .field("scale", scale)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl NormalMap {
+    pub fn image(image: Arc<ImageTexture>, mapping: UvMap) -> Self {
+        Self::Image {
+            image,
+            mapping,
+            strength: 1.0,
+        }
+    }
+
+    pub fn bump(height: impl Fn(Pos3) -> f32 + Send + Sync + 'static, scale: f32) -> Self {
+        Self::Bump {
+            height: Arc::new(height),
+            scale,
+        }
+    }
+
+    /// Returns the perturbed normal at a world-space point. `t_inverted` is the world-to-object
+    /// transform of the surface.
+    pub fn perturb(&self, point: Pos3, frame: &TangentFrame, t_inverted: &Mat4) -> Vec3 {
+        match self {
+            Self::Image {
+                image,
+                mapping,
+                strength,
+            } => {
+                let (u, v) = mapping.map(t_inverted * point);
+                let c = image.sample(u, v);
+                let local = Vec3::new(
+                    ((c.0 * 2.0) - 1.0) * strength,
+                    ((c.1 * 2.0) - 1.0) * strength,
+                    (c.2 * 2.0) - 1.0,
+                );
+
+                frame.to_world(local).to_normalized()
+            }
+            Self::Bump { height, scale } => {
+                let h = |p: Pos3| height(t_inverted * p);
+
+                let d_t = (h(point + (frame.tangent * BUMP_EPSILON))
+                    - h(point - (frame.tangent * BUMP_EPSILON)))
+                    / (2.0 * BUMP_EPSILON);
+                let d_b = (h(point + (frame.bitangent * BUMP_EPSILON))
+                    - h(point - (frame.bitangent * BUMP_EPSILON)))
+                    / (2.0 * BUMP_EPSILON);
+
+                (frame.normal - ((frame.tangent * d_t) + (frame.bitangent * d_b)) * *scale)
+                    .to_normalized()
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_normal_map_flat() {
+    // (0.5, 0.5, 1.0) is the "straight out" color in a tangent-space normal map
+    let image = ImageTexture::from_pixels(1, 1, vec![crate::Color(0.5, 0.5, 1.0)]);
+    let map = NormalMap::image(Arc::new(image), UvMap::Spherical);
+    let frame = TangentFrame::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 0.0));
+    let identity = crate::identity_matrix!();
+
+    let normal = map.perturb(Pos3::new(0.0, 0.0, -1.0), &frame, &identity);
+    assert_eq!(normal, frame.normal);
+
+    let map = NormalMap::bump(|_| 1.0, 1.0);
+    let normal = map.perturb(Pos3::new(0.0, 0.0, -1.0), &frame, &identity);
+    assert_eq!(normal, frame.normal);
+}
+
+#[test]
+pub fn test_normal_map_bump_slope() {
+    // height rises to the right, so the normal should lean left
+    let map = NormalMap::bump(|p| p.x, 1.0);
+    let frame = TangentFrame::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 0.0));
+
+    let normal = map.perturb(
+        Pos3::new(0.0, 0.0, -1.0),
+        &frame,
+        &crate::identity_matrix!(),
+    );
+
+    assert_eq!(normal, Vec3::new(-1.0, 0.0, -1.0).to_normalized());
+}
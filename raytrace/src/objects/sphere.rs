@@ -1,50 +1,278 @@
 #![allow(clippy::approx_constant)]
 
-use crate::{Pos3, Matrix, Vec3, objects::material::Material};
+use std::{borrow::Cow, sync::Arc};
+
+use crate::{Pos3, Mat4, Vec3, media::Medium, objects::material::Material, primitives::frame::TangentFrame};
+use crate::animation::TransformTrack;
 
 #[derive(Debug, Clone)]
 pub struct Sphere {
-    pub transform: Matrix,
-    pub t_inverted: Matrix,
-    pub t_transposed: Matrix,
-    pub t_invert_transp: Matrix,
+    // private so the cached matrices can only change together, see `set_transform`
+    transform: Mat4,
+    t_inverted: Mat4,
+    t_transposed: Mat4,
+    t_invert_transp: Mat4,
     pub material: Material,
+    /// When present, the sphere is an invisible boundary around this medium rather than a solid
+    /// surface
+    pub medium: Option<Arc<Medium>>,
+    /// Makes the sphere move over time, replacing `transform` for rays with a `time`. Only set
+    /// while rendering with motion blur, since every ray has to work out where the sphere is,
+    /// see `t_inverted_at`.
+    pub motion: Option<Arc<TransformTrack>>,
 }
 
 impl Sphere {
+    /// Panics if `transform` can't be inverted, see `try_new`
     pub fn new(
-        transform: Matrix,
+        transform: Mat4,
         material: Material
     ) -> Self {
-        let t_inverted = transform.inverted().unwrap();
+        Self::try_new(transform, material).expect("a sphere's transform must be invertible")
+    }
+
+    /// Returns `None` if `transform` can't be inverted, e.g. when it scales an axis to zero
+    pub fn try_new(transform: Mat4, material: Material) -> Option<Self> {
+        let t_inverted = transform.inverted()?;
         let t_transposed = transform.transposed();
         let t_invert_transp = t_inverted.transposed();
-        Self {
+        Some(Self {
             transform,
             t_inverted,
             t_transposed,
             t_invert_transp,
             material,
-        }
+            medium: None,
+            motion: None,
+        })
+    }
+
+    /// Replaces the object-to-world transform, along with everything cached from it. Panics if
+    /// it can't be inverted, see `try_set_transform`.
+    pub fn set_transform(self, transform: Mat4) -> Self {
+        self.try_set_transform(transform)
+            .expect("a sphere's transform must be invertible")
+    }
+
+    /// Like `set_transform`, returning `None` if `transform` can't be inverted
+    pub fn try_set_transform(self, transform: Mat4) -> Option<Self> {
+        Some(Self {
+            medium: self.medium,
+            motion: self.motion,
+            ..Self::try_new(transform, self.material)?
+        })
+    }
+
+    /// Object to world space
+    pub fn transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    /// World to object space
+    pub fn t_inverted(&self) -> &Mat4 {
+        &self.t_inverted
+    }
+
+    pub fn t_transposed(&self) -> &Mat4 {
+        &self.t_transposed
+    }
+
+    /// Takes object-space normals to world space
+    pub fn t_invert_transp(&self) -> &Mat4 {
+        &self.t_invert_transp
+    }
+
+    pub fn with_medium(mut self, medium: Arc<Medium>) -> Self {
+        self.medium = Some(medium);
+        self
     }
 
-    pub fn set_transform(mut self, transform: Matrix) -> Self {
-        self.transform = transform;
+    pub fn with_motion(mut self, motion: Arc<TransformTrack>) -> Self {
+        self.motion = Some(motion);
         self
     }
 
+    /// `t_inverted` where `motion` puts the sphere at `time`. Rays only need this to find their
+    /// hits, so the rest of the sphere is left alone until one of them is shaded. `None` while
+    /// the sphere is scaled flat and can't be hit.
+    pub fn t_inverted_at(&self, time: f32) -> Option<Mat4> {
+        match &self.motion {
+            Some(motion) => motion.inverse_matrix(time),
+            None => Some(self.t_inverted),
+        }
+    }
+
+    /// The sphere where `motion` puts it at `time`, borrowing everything that doesn't move. One
+    /// that doesn't move is where its own transform puts it, as is a moving one while it's scaled
+    /// flat, since no ray can hit it then.
+    pub fn at_time(&self, time: f32) -> SpherePose<'_> {
+        let posed = self
+            .motion
+            .as_ref()
+            .and_then(|motion| Some((motion.matrix(time), motion.inverse_matrix(time)?)));
+        let (transform, t_inverted) = posed.unwrap_or((self.transform, self.t_inverted));
+
+        SpherePose {
+            material: &self.material,
+            transform,
+            t_inverted,
+            t_invert_transp: t_inverted.transposed(),
+        }
+    }
+
+    fn pose(&self) -> SpherePose<'_> {
+        SpherePose {
+            material: &self.material,
+            transform: self.transform,
+            t_inverted: self.t_inverted,
+            t_invert_transp: self.t_invert_transp,
+        }
+    }
+
     pub fn normal_at(&self, point: Pos3) -> Vec3 {
-        let object_point = &self.t_inverted * point;
+        self.pose().normal_at(point)
+    }
+
+    /// Returns the direction of increasing `u` under a spherical UV mapping, i.e. eastward around
+    /// the Y axis. Falls back to the X axis at the poles.
+    pub fn tangent_at(&self, point: Pos3) -> Vec3 {
+        self.pose().tangent_at(point)
+    }
+
+    pub fn tangent_frame_at(&self, point: Pos3) -> TangentFrame {
+        self.pose().tangent_frame_at(point)
+    }
+
+    /// Returns the normal used for lighting, which is the geometric normal perturbed by the
+    /// material's normal map if it has one
+    pub fn shading_normal_at(&self, point: Pos3) -> Vec3 {
+        self.pose().shading_normal_at(point)
+    }
+
+    /// Returns the material with any texture resolved to a flat color at the given world-space
+    /// point
+    pub fn material_at(&self, point: Pos3) -> Cow<'_, Material> {
+        self.pose().material_at(point)
+    }
+}
+
+/// A sphere at one moment, with the matrices for where it is then, see `Sphere::at_time`
+#[derive(Debug, Clone, Copy)]
+pub struct SpherePose<'a> {
+    pub material: &'a Material,
+    transform: Mat4,
+    t_inverted: Mat4,
+    t_invert_transp: Mat4,
+}
+
+impl<'a> SpherePose<'a> {
+    pub fn normal_at(&self, point: Pos3) -> Vec3 {
+        let object_point = self.t_inverted * point;
         let dist = object_point - Pos3::new(0.0, 0.0, 0.0);
 
-        (&self.t_invert_transp * dist).to_normalized()
+        (self.t_invert_transp * dist).to_normalized()
+    }
+
+    /// See `Sphere::tangent_at`
+    pub fn tangent_at(&self, point: Pos3) -> Vec3 {
+        let object_point = self.t_inverted * point;
+        let object_tangent = if object_point.x.abs() < 1e-6 && object_point.z.abs() < 1e-6 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(-object_point.z, 0.0, object_point.x)
+        };
+
+        (self.transform * object_tangent).to_normalized()
+    }
+
+    pub fn tangent_frame_at(&self, point: Pos3) -> TangentFrame {
+        TangentFrame::new(self.normal_at(point), self.tangent_at(point))
+    }
+
+    /// See `Sphere::shading_normal_at`
+    pub fn shading_normal_at(&self, point: Pos3) -> Vec3 {
+        match &self.material.normal_map {
+            Some(map) => map.perturb(point, &self.tangent_frame_at(point), &self.t_inverted),
+            None => self.normal_at(point),
+        }
+    }
+
+    /// See `Sphere::material_at`
+    pub fn material_at(&self, point: Pos3) -> Cow<'a, Material> {
+        if self.material.texture.is_none() {
+            return Cow::Borrowed(self.material);
+        }
+
+        let mut material = self.material.clone();
+        material.color = self.material.color_at(self.t_inverted * point);
+        material.texture = None;
+
+        Cow::Owned(material)
     }
 }
 
 #[test]
 pub fn test_normal() {
-    let sphere = Sphere::new(Matrix::translation(0.0, 1.0, 0.0), Material::default());
+    let sphere = Sphere::new(Mat4::translation(0.0, 1.0, 0.0), Material::default());
     let normal = sphere.normal_at(Pos3::new(0.0, 1.70711, -0.70711));
 
     assert_eq!(normal, Vec3::new(0.0, 0.70711, -0.70711));
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn test_tangent_frame() {
+    let sphere = Sphere::new(Mat4::translation(0.0, 1.0, 0.0), Material::default());
+    let frame = sphere.tangent_frame_at(Pos3::new(0.0, 1.0, -1.0));
+
+    assert_eq!(frame.normal, Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(frame.tangent, Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(frame.bitangent, Vec3::new(0.0, 1.0, 0.0));
+}
+
+#[test]
+pub fn test_set_transform_updates_caches() {
+    use crate::Ray;
+
+    let moved = Sphere::new(Mat4::IDENTITY, Material::default())
+        .set_transform(Mat4::translation(0.0, 0.0, 3.0) * Mat4::scaling(2.0, 2.0, 2.0));
+
+    let fresh = Sphere::new(*moved.transform(), Material::default());
+    assert_eq!(*moved.t_inverted(), *fresh.t_inverted());
+    assert_eq!(*moved.t_transposed(), *fresh.t_transposed());
+    assert_eq!(*moved.t_invert_transp(), *fresh.t_invert_transp());
+
+    // intersections and normals both see the new transform, not the one it was created with
+    let ray = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    let hits = ray.sphere_intersect(&Arc::new(moved.clone()));
+    assert_eq!((hits[0].t, hits[1].t), (6.0, 10.0));
+    assert_eq!(moved.normal_at(Pos3::new(2.0, 0.0, 3.0)), Vec3::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+pub fn test_at_time() {
+    use crate::animation::{Interpolation, Keyframe, Pose, Track};
+
+    // squashed flat along X halfway through
+    let poses = [(0.0, 2.0), (1.0, 0.0), (2.0, -2.0)].map(|(time, x)| Keyframe {
+        time,
+        value: Pose {
+            scale: Vec3::new(x, 1.0, 1.0),
+            ..Default::default()
+        },
+    });
+    let track = TransformTrack::new(
+        Mat4::translation(0.0, 1.0, 0.0),
+        Track::new(poses.to_vec(), Interpolation::Linear),
+    )
+    .unwrap();
+    let sphere = Sphere::new(*track.base(), Material::default()).with_motion(Arc::new(track));
+
+    let stretched = Mat4::scaling(2.0, 1.0, 1.0) * *sphere.transform();
+    let still = Sphere::new(stretched, Material::default());
+    let point = Pos3::new(0.0, 1.70711, -0.70711);
+    assert_eq!(sphere.at_time(0.0).normal_at(point), still.normal_at(point));
+    assert_eq!(sphere.at_time(0.0).tangent_at(point), still.tangent_at(point));
+
+    // nothing can hit it while it's flat, but shading it anyway doesn't panic
+    assert_eq!(sphere.at_time(1.0).normal_at(point), sphere.normal_at(point));
+}
@@ -0,0 +1,217 @@
+use std::{fmt, path::Path, sync::Arc};
+
+use image::{DynamicImage, ImageResult};
+
+use crate::{objects::uv::UvMap, Color, Pos3};
+
+/// How texels are looked up between pixel centers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    #[default]
+    Nearest,
+    Bilinear,
+}
+
+/// What happens to texture coordinates outside of [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    Clamp,
+}
+
+impl Wrap {
+    fn apply(&self, index: isize, size: usize) -> usize {
+        match self {
+            Wrap::Repeat => index.rem_euclid(size as isize) as usize,
+            Wrap::Clamp => index.clamp(0, size as isize - 1) as usize,
+        }
+    }
+}
+
+/// A grid of colors sampled with (u, v) coordinates, where (0, 0) is the bottom left of the image
+/// and (1, 1) is the top right.
+#[derive(Clone)]
+pub struct ImageTexture {
+    pixels: Vec<Color>,
+    width: usize,
+    height: usize,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+impl fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageTexture")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("filter", &self.filter)
+            .field("wrap", &self.wrap)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ImageTexture {
+    /// Stores pixels in row-major order, starting from the top left
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count must match texture dimensions"
+        );
+
+        Self {
+            pixels,
+            width,
+            height,
+            filter: Filter::default(),
+            wrap: Wrap::default(),
+        }
+    }
+
+    /// Loads any image format supported by the `image` crate. Integer formats are assumed to hold
+    /// sRGB colors and are converted to linear, while float formats are used as-is.
+    pub fn open(path: impl AsRef<Path>) -> ImageResult<Self> {
+        Self::load(path, true)
+    }
+
+    /// Loads an image holding non-color data, like a normal map, without any conversion
+    pub fn open_linear(path: impl AsRef<Path>) -> ImageResult<Self> {
+        Self::load(path, false)
+    }
+
+    fn load(path: impl AsRef<Path>, srgb: bool) -> ImageResult<Self> {
+        let image = image::open(path)?;
+        let decode = srgb
+            && !matches!(
+                image,
+                DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+            );
+
+        let image = image.into_rgb32f();
+        let (width, height) = image.dimensions();
+        let pixels = image
+            .pixels()
+            .map(|p| {
+                let c = Color(p[0], p[1], p[2]);
+                if decode {
+                    c.to_linear()
+                } else {
+                    c
+                }
+            })
+            .collect();
+
+        Ok(Self::from_pixels(width as usize, height as usize, pixels))
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the texel at the given column and row, after applying the wrap mode
+    pub fn texel(&self, x: isize, y: isize) -> Color {
+        let x = self.wrap.apply(x, self.width);
+        let y = self.wrap.apply(y, self.height);
+
+        self.pixels[(y * self.width) + x]
+    }
+
+    pub fn sample(&self, u: f32, v: f32) -> Color {
+        // flip v since image rows start at the top
+        let x = u * self.width as f32;
+        let y = (1.0 - v) * self.height as f32;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as isize, y.floor() as isize),
+            Filter::Bilinear => {
+                // offset by half a texel so that weights are relative to pixel centers
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+
+                let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+
+                top * (1.0 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+/// An image applied to an object via a UV mapping
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub image: Arc<ImageTexture>,
+    pub mapping: UvMap,
+}
+
+impl Texture {
+    pub fn new(image: Arc<ImageTexture>, mapping: UvMap) -> Self {
+        Self { image, mapping }
+    }
+
+    /// Expects a point in object space
+    pub fn color_at(&self, object_point: Pos3) -> Color {
+        let (u, v) = self.mapping.map(object_point);
+        self.image.sample(u, v)
+    }
+}
+
+#[cfg(test)]
+fn checker_2x2() -> ImageTexture {
+    ImageTexture::from_pixels(
+        2,
+        2,
+        vec![Color::BLACK, Color::WHITE, Color::WHITE, Color::BLACK],
+    )
+}
+
+#[test]
+pub fn test_texture_nearest() {
+    let tex = checker_2x2();
+
+    assert_eq!(tex.sample(0.25, 0.75), Color::BLACK);
+    assert_eq!(tex.sample(0.75, 0.75), Color::WHITE);
+    assert_eq!(tex.sample(0.25, 0.25), Color::WHITE);
+
+    // repeats by default
+    assert_eq!(tex.sample(1.25, 0.75), Color::BLACK);
+    assert_eq!(tex.sample(-0.25, 0.75), Color::WHITE);
+
+    let tex = tex.with_wrap(Wrap::Clamp);
+    assert_eq!(tex.sample(1.25, 0.75), Color::WHITE);
+    assert_eq!(tex.sample(-0.25, 0.75), Color::BLACK);
+}
+
+#[test]
+pub fn test_texture_bilinear() {
+    let tex = checker_2x2().with_filter(Filter::Bilinear);
+
+    // pixel centers are exact
+    assert_eq!(tex.sample(0.25, 0.75), Color::BLACK);
+    // dead center is an even mix of all 4
+    assert_eq!(tex.sample(0.5, 0.5), Color(0.5, 0.5, 0.5));
+    // halfway between 2 pixel centers on the top row
+    assert_eq!(tex.sample(0.5, 0.75), Color(0.5, 0.5, 0.5));
+
+    let tex = tex.with_wrap(Wrap::Clamp);
+    // the left edge of the top row doesn't blend with the right side when clamped
+    assert_eq!(tex.sample(0.0, 0.75), Color::BLACK);
+}
@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+
+use crate::Pos3;
+
+/// Projects an object-space point onto 2D texture coordinates. `u` runs left to right and `v`
+/// runs bottom to top.
+///
+/// Planar and cylindrical mappings return unbounded coordinates along their open axes, so how
+/// they tile is left to the texture's `Wrap` mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UvMap {
+    #[default]
+    Spherical,
+    /// Projects along the Y axis, using the X and Z coordinates directly
+    Planar,
+    /// Wraps around the Y axis, with `v` being the height of the point
+    Cylindrical,
+    /// Expects the texture to be laid out as a horizontal cross, 4 faces wide and 3 faces tall,
+    /// where "front" is the -Z face that the default camera looks at:
+    ///
+    /// ```text
+    ///     [up]
+    /// [left][front][right][back]
+    ///     [down]
+    /// ```
+    Cube,
+}
+
+impl UvMap {
+    pub fn map(&self, point: Pos3) -> (f32, f32) {
+        match self {
+            UvMap::Spherical => spherical(point),
+            UvMap::Planar => (point.x, point.z),
+            UvMap::Cylindrical => (azimuth(point), point.y),
+            UvMap::Cube => cube(point),
+        }
+    }
+}
+
+/// Angle around the Y axis, remapped to [0, 1)
+fn azimuth(point: Pos3) -> f32 {
+    let theta = point.x.atan2(point.z);
+    let raw_u = theta / (2.0 * PI);
+
+    1.0 - (raw_u + 0.5)
+}
+
+fn spherical(point: Pos3) -> (f32, f32) {
+    let radius = (point.x.powi(2) + point.y.powi(2) + point.z.powi(2)).sqrt();
+    let phi = (point.y / radius).clamp(-1.0, 1.0).acos();
+
+    (azimuth(point), 1.0 - (phi / PI))
+}
+
+fn cube(point: Pos3) -> (f32, f32) {
+    let abs_x = point.x.abs();
+    let abs_y = point.y.abs();
+    let abs_z = point.z.abs();
+    let largest = abs_x.max(abs_y).max(abs_z);
+
+    // push the point out onto the surface of the [-1, 1] cube so spheres map cleanly
+    let Pos3 { x, y, z } = Pos3::new(point.x / largest, point.y / largest, point.z / largest);
+
+    // (column, row) of the face in the cross layout, counted from the bottom left
+    let ((col, row), (u, v)) = if largest == abs_x && point.x > 0.0 {
+        ((2.0, 1.0), (z + 1.0, y + 1.0))
+    } else if largest == abs_x {
+        ((0.0, 1.0), (1.0 - z, y + 1.0))
+    } else if largest == abs_y && point.y > 0.0 {
+        ((1.0, 2.0), (x + 1.0, z + 1.0))
+    } else if largest == abs_y {
+        ((1.0, 0.0), (x + 1.0, 1.0 - z))
+    } else if point.z > 0.0 {
+        ((3.0, 1.0), (1.0 - x, y + 1.0))
+    } else {
+        ((1.0, 1.0), (x + 1.0, y + 1.0))
+    };
+
+    // face-local coordinates are in [0, 2], inset slightly so filtering can't bleed across faces
+    let u = (u / 2.0).clamp(0.001, 0.999);
+    let v = (v / 2.0).clamp(0.001, 0.999);
+
+    ((col + u) / 4.0, (row + v) / 3.0)
+}
+
+#[test]
+pub fn test_uv_spherical() {
+    let cases = [
+        (Pos3::new(0.0, 0.0, -1.0), (0.0, 0.5)),
+        (Pos3::new(1.0, 0.0, 0.0), (0.25, 0.5)),
+        (Pos3::new(0.0, 0.0, 1.0), (0.5, 0.5)),
+        (Pos3::new(-1.0, 0.0, 0.0), (0.75, 0.5)),
+        (Pos3::new(0.0, 1.0, 0.0), (0.5, 1.0)),
+        (Pos3::new(0.0, -1.0, 0.0), (0.5, 0.0)),
+    ];
+
+    for (point, (u, v)) in cases {
+        let (ru, rv) = UvMap::Spherical.map(point);
+        assert!(crate::float_eq(ru, u) && crate::float_eq(rv, v), "{point:?}");
+    }
+}
+
+#[test]
+pub fn test_uv_cube() {
+    // center of the front (-Z) face
+    let (u, v) = UvMap::Cube.map(Pos3::new(0.0, 0.0, -1.0));
+    assert!(crate::float_eq(u, 1.5 / 4.0) && crate::float_eq(v, 1.5 / 3.0));
+
+    // center of the up face
+    let (u, v) = UvMap::Cube.map(Pos3::new(0.0, 1.0, 0.0));
+    assert!(crate::float_eq(u, 1.5 / 4.0) && crate::float_eq(v, 2.5 / 3.0));
+}
@@ -0,0 +1,168 @@
+use crate::{
+    aov::PrimaryHit,
+    primitives::frame::TangentFrame,
+    sampling::{cosine_hemisphere, Rng},
+    scene::SURFACE_EPSILON,
+    Color, Object, Pos3, Ray, Scene, Vec3,
+};
+
+#[cfg(test)]
+use crate::{objects::material::Material, objects::Sphere, Mat4};
+#[cfg(test)]
+use std::sync::Arc;
+
+/// Controls how ambient occlusion is estimated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AoSettings {
+    /// Number of rays cast from each point
+    pub samples: usize,
+    /// Objects further away than this don't occlude
+    pub max_distance: f32,
+}
+
+impl Default for AoSettings {
+    fn default() -> Self {
+        Self {
+            samples: 16,
+            max_distance: 1.0,
+        }
+    }
+}
+
+impl Scene {
+    /// Returns the fraction of cosine-weighted rays leaving `point` that escape within
+    /// `settings.max_distance`, from 0.0 (fully enclosed) to 1.0 (fully open)
+    pub fn ambient_occlusion(
+        &self,
+        point: Pos3,
+        normal: Vec3,
+        settings: &AoSettings,
+        rng: &mut Rng,
+    ) -> f32 {
+        self.ambient_occlusion_at(point, normal, 0.0, settings, rng)
+    }
+
+    /// Like `ambient_occlusion`, with the rays sent at `time` for scenes where things move
+    pub fn ambient_occlusion_at(
+        &self,
+        point: Pos3,
+        normal: Vec3,
+        time: f32,
+        settings: &AoSettings,
+        rng: &mut Rng,
+    ) -> f32 {
+        if settings.samples == 0 {
+            return 1.0;
+        }
+
+        let frame = TangentFrame::from_normal(normal);
+        let origin = point + (normal * SURFACE_EPSILON);
+
+        let open = (0..settings.samples)
+            .filter(|_| {
+                let dir = frame.to_world(cosine_hemisphere(rng));
+                let ray = Ray::new(origin, dir).with_time(time);
+                !self.is_occluded(&ray, settings.max_distance)
+            })
+            .count();
+
+        open as f32 / settings.samples as f32
+    }
+
+    /// Shades the first hit along `ray` by its ambient occlusion alone, ignoring lights and
+    /// materials. Rays that hit nothing are fully open.
+    pub fn trace_ao(&self, ray: Ray, settings: &AoSettings, rng: &mut Rng) -> Color {
+        self.trace_ao_hit(ray, settings, rng).0
+    }
+
+    /// Like `trace_ao`, also returning the closest hit
+    pub(crate) fn trace_ao_hit(
+        &self,
+        ray: Ray,
+        settings: &AoSettings,
+        rng: &mut Rng,
+    ) -> (Color, PrimaryHit) {
+        let ray = Ray::new(ray.origin, ray.dir.to_normalized()).with_time(ray.time);
+        let intersects = self.get_intersections(&ray, SURFACE_EPSILON, f32::MAX);
+
+        let Some(hit) = self.get_closest(intersects) else {
+            return (Color::WHITE, PrimaryHit { ray, hit: None });
+        };
+
+        let Object::Sphere(obj) = &hit.obj;
+        let obj = obj.at_time(ray.time);
+        let point = ray.position(hit.t);
+        let mut normal = obj.normal_at(point);
+        if normal * ray.dir > 0.0 {
+            normal = -normal;
+        }
+
+        let open = self.ambient_occlusion_at(point, normal, ray.time, settings, rng);
+        let primary = PrimaryHit {
+            ray,
+            hit: Some(hit),
+        };
+        (Color(open, open, open), primary)
+    }
+
+    /// Used by `compute_lighting` to darken the ambient term in crevices when
+    /// `Scene::ao` is set. There's no generator to thread through there, so the
+    /// rays are seeded from the point itself, which keeps the result stable between renders.
+    pub(crate) fn ambient_factor(&self, point: Pos3, normal: Vec3, time: f32) -> f32 {
+        let Some(settings) = &self.ao else {
+            return 1.0;
+        };
+
+        let seed = (point.x.to_bits() as u64)
+            ^ ((point.y.to_bits() as u64) << 21)
+            ^ ((point.z.to_bits() as u64) << 42);
+
+        self.ambient_occlusion_at(point, normal, time, settings, &mut Rng::new(seed, 0))
+    }
+}
+
+#[test]
+pub fn test_ao_open() {
+    let scene = Scene::default();
+    let settings = AoSettings::default();
+    let mut rng = Rng::new(0, 0);
+
+    // the default scene's spheres are nested, so the outside of the outer one sees nothing
+    let open = scene.ambient_occlusion(
+        Pos3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        &settings,
+        &mut rng,
+    );
+    assert_eq!(open, 1.0);
+}
+
+#[test]
+pub fn test_ao_contact() {
+    let scene = Scene {
+        spheres: vec![
+            Arc::new(Sphere::new(crate::identity_matrix!(), Material::default())),
+            Arc::new(Sphere::new(
+                Mat4::translation(0.0, -2.0, 0.0),
+                Material::default(),
+            )),
+        ],
+        ..Default::default()
+    };
+    let mut settings = AoSettings {
+        samples: 256,
+        max_distance: 10.0,
+    };
+    let mut rng = Rng::new(0, 0);
+
+    // right next to where the 2 spheres touch, most rays are blocked
+    let point = Pos3::new(0.0, -1.0, 0.0);
+    let normal = Vec3::new(0.0, -1.0, 0.0);
+    let open = scene.ambient_occlusion(point, normal, &settings, &mut rng);
+    assert!(open < 0.5, "{open}");
+
+    // but they're all open once the other sphere is out of reach
+    settings.max_distance = 0.0001;
+    let open = scene.ambient_occlusion(point, normal, &settings, &mut rng);
+    assert_eq!(open, 1.0);
+}
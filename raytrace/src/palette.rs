@@ -0,0 +1,215 @@
+//! Reduces images to a few colors, for formats like GIF that store an index into a palette
+//! instead of each pixel's color
+
+use image::RgbImage;
+
+/// What happens to the difference between a pixel and the palette color it's drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    /// Every pixel takes its nearest palette color, which leaves bands across smooth gradients
+    #[default]
+    None,
+    /// Floyd-Steinberg error diffusion, spreading each pixel's error over the neighbours that
+    /// haven't been drawn yet, so areas average out to the right color
+    FloydSteinberg,
+}
+
+/// Up to `Palette::MAX_COLORS` 8-bit sRGB colors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub const MAX_COLORS: usize = 256;
+
+    /// How many pixels `from_images` looks at, at most. Spread evenly over every image, that's
+    /// plenty to find the main colors and keeps the sorting in `median_cut` quick.
+    const SAMPLE_LIMIT: usize = 1 << 18;
+
+    /// Median cut: starting from a single box around every color, keep splitting the box with the
+    /// widest range in any channel at its median, until there are `size` boxes or nothing left to
+    /// split. Each box becomes the average of the colors in it.
+    pub fn median_cut(colors: &[[u8; 3]], size: usize) -> Palette {
+        let size = size.clamp(1, Self::MAX_COLORS);
+        let mut boxes = vec![colors.to_vec()];
+
+        while boxes.len() < size {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .map(|(i, b)| {
+                    let (channel, range) = widest_channel(b);
+                    (i, channel, range)
+                })
+                .max_by_key(|&(_, _, range)| range);
+            let Some((index, channel, range)) = widest else {
+                break;
+            };
+            if range == 0 {
+                break;
+            }
+
+            let mut colors = boxes.swap_remove(index);
+            colors.sort_unstable_by_key(|c| c[channel]);
+            // split where the channel changes, so no value ends up in both halves
+            let median = colors[colors.len() / 2][channel];
+            let mut split = colors.partition_point(|c| c[channel] < median);
+            if split == 0 {
+                split = colors.partition_point(|c| c[channel] <= median);
+            }
+
+            let upper = colors.split_off(split);
+            boxes.push(colors);
+            boxes.push(upper);
+        }
+
+        let colors = boxes.iter().filter(|b| !b.is_empty()).map(|b| average(b));
+        Self::new(colors.collect())
+    }
+
+    /// A palette shared by every image, so colors don't flicker from one frame to the next
+    pub fn from_images(images: &[RgbImage], size: usize) -> Palette {
+        let total: usize = images.iter().map(|i| i.pixels().len()).sum();
+        let step = total.div_ceil(Self::SAMPLE_LIMIT).max(1);
+        let colors: Vec<[u8; 3]> = images
+            .iter()
+            .flat_map(|i| i.pixels())
+            .step_by(step)
+            .map(|p| p.0)
+            .collect();
+
+        Self::median_cut(&colors, size)
+    }
+
+    /// Falls back to a single black entry when `colors` is empty
+    pub fn new(mut colors: Vec<[u8; 3]>) -> Palette {
+        if colors.is_empty() {
+            colors.push([0, 0, 0]);
+        }
+        colors.truncate(Self::MAX_COLORS);
+
+        Self { colors }
+    }
+
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// The index of the closest color, by distance in sRGB
+    pub fn nearest(&self, color: [f32; 3]) -> u8 {
+        let distance = |c: &[u8; 3]| {
+            (0..3)
+                .map(|i| (c[i] as f32 - color[i]).powi(2))
+                .sum::<f32>()
+        };
+
+        let (index, _) = self
+            .colors
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .expect("a palette always has at least one color");
+        index as u8
+    }
+
+    /// The palette index for every pixel, row by row
+    pub fn map(&self, image: &RgbImage, dither: Dither) -> Vec<u8> {
+        let pixels = image.pixels().map(|p| p.0.map(|c| c as f32));
+        match dither {
+            Dither::None => pixels.map(|p| self.nearest(p)).collect(),
+            Dither::FloydSteinberg => self.diffuse(image.width() as usize, pixels),
+        }
+    }
+
+    fn diffuse(&self, width: usize, pixels: impl Iterator<Item = [f32; 3]>) -> Vec<u8> {
+        // errors carried into this row and the next, padded by a pixel at either end
+        let mut row = vec![[0.0; 3]; width + 2];
+        let mut below = vec![[0.0; 3]; width + 2];
+        let mut indices = Vec::new();
+
+        for (i, pixel) in pixels.enumerate() {
+            let x = i % width;
+            if x == 0 && i > 0 {
+                row = std::mem::replace(&mut below, vec![[0.0; 3]; width + 2]);
+            }
+
+            let wanted: [f32; 3] =
+                std::array::from_fn(|c| (pixel[c] + row[x + 1][c]).clamp(0.0, 255.0));
+            let index = self.nearest(wanted);
+            indices.push(index);
+
+            let got = self.colors[index as usize];
+            for c in 0..3 {
+                let error = wanted[c] - got[c] as f32;
+                row[x + 2][c] += error * 7.0 / 16.0;
+                below[x][c] += error * 3.0 / 16.0;
+                below[x + 1][c] += error * 5.0 / 16.0;
+                below[x + 2][c] += error / 16.0;
+            }
+        }
+
+        indices
+    }
+}
+
+/// The channel whose values spread the furthest, and how far
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = colors.iter().map(|c| c[channel]);
+            let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, range)
+        })
+        .max_by_key(|&(_, range)| range)
+        .expect("there are always 3 channels")
+}
+
+fn average(colors: &[[u8; 3]]) -> [u8; 3] {
+    let mut total = [0u64; 3];
+    for color in colors {
+        for c in 0..3 {
+            total[c] += color[c] as u64;
+        }
+    }
+
+    total.map(|t| ((t as f64 / colors.len() as f64).round()) as u8)
+}
+
+#[test]
+pub fn test_palette_quantize() {
+    // a horizontal gradient from black to red
+    let image = RgbImage::from_fn(64, 4, |x, _| image::Rgb([(x * 4) as u8, 0, 0]));
+
+    let palette = Palette::from_images(std::slice::from_ref(&image), 4);
+    assert_eq!(palette.colors().len(), 4);
+    assert!(palette.colors().iter().all(|c| c[1] == 0 && c[2] == 0));
+
+    // a color that's already in the palette maps to itself
+    let exact = Palette::median_cut(&[[10, 20, 30], [200, 100, 0]], 16);
+    assert_eq!(exact.colors().len(), 2);
+    assert_eq!(
+        exact.colors()[exact.nearest([200.0, 100.0, 0.0]) as usize],
+        [200, 100, 0]
+    );
+    assert_eq!(Palette::median_cut(&[], 8).colors(), &[[0, 0, 0]]);
+
+    // dithering keeps the average brightness of each patch close to the original, where the
+    // nearest color alone is off by up to half a step between palette entries
+    let patch = |indices: &[u8], left: usize| {
+        let sum: f32 = (0..4)
+            .flat_map(|y| (left..left + 8).map(move |x| y * 64 + x))
+            .map(|i| palette.colors()[indices[i] as usize][0] as f32)
+            .sum();
+        sum / 32.0
+    };
+    let error = |indices: &[u8]| {
+        (0..64)
+            .step_by(8)
+            .map(|left| (patch(indices, left) - (left * 4 + 14) as f32).abs())
+            .sum::<f32>()
+    };
+    let plain = palette.map(&image, Dither::None);
+    let dithered = palette.map(&image, Dither::FloydSteinberg);
+    assert!(error(&dithered) < error(&plain) / 2.0);
+}
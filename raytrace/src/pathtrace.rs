@@ -0,0 +1,220 @@
+use std::f32::consts::PI;
+
+use crate::{
+    aov::PrimaryHit,
+    sampling::{power_heuristic, Rng},
+    scene::SURFACE_EPSILON,
+    Color, Object, Pos3, Ray, Scene, Vec3,
+};
+
+#[cfg(test)]
+use crate::{environment::Environment, objects::material::Material, objects::Sphere};
+#[cfg(test)]
+use std::sync::Arc;
+
+/// Bounces before paths start being randomly terminated based on their remaining contribution
+const ROULETTE_START: usize = 3;
+
+impl Scene {
+    /// Estimates the light arriving along `ray` by following a single random path of up to
+    /// `depth` bounces. Many samples need to be averaged per pixel for the result to converge.
+    ///
+    /// Direct light from point lights, the sun and the environment is gathered at every bounce.
+    /// Point lights aren't attenuated, and lights are scaled so that a white Lambertian surface
+    /// matches the diffuse term of `compute_lighting`. The environment is importance sampled and
+    /// combined with BSDF sampling via multiple importance sampling.
+    ///
+    /// Paths can also scatter inside participating media, which counts as a bounce.
+    pub fn trace_path(&self, ray: Ray, depth: usize, rng: &mut Rng) -> Color {
+        self.trace_path_hit(ray, depth, rng).0
+    }
+
+    /// Like `trace_path`, also returning the closest hit of the camera ray
+    pub(crate) fn trace_path_hit(
+        &self,
+        ray: Ray,
+        depth: usize,
+        rng: &mut Rng,
+    ) -> (Color, PrimaryHit) {
+        let time = ray.time;
+        let mut ray = Ray::new(ray.origin, ray.dir.to_normalized()).with_time(time);
+        let mut primary = PrimaryHit {
+            ray: ray.clone(),
+            hit: None,
+        };
+        let mut throughput = Color::WHITE;
+        let mut radiance = Color::BLACK;
+        // pdf of the sample that produced the current ray, or None for camera rays
+        let mut prev_pdf: Option<f32> = None;
+
+        for bounce in 0..=depth {
+            let intersects = self.get_intersections(&ray, SURFACE_EPSILON, f32::MAX);
+            let closest = self.get_closest(intersects);
+            let t_hit = closest.as_ref().map_or(f32::MAX, |hit| hit.t);
+            if bounce == 0 {
+                primary.hit = closest.clone();
+            }
+
+            let (interaction, weight) = self.sample_media(&ray, t_hit, rng);
+            throughput = throughput * weight;
+
+            let (origin, wi, pdf) = if let Some((t, medium)) = interaction {
+                let point = ray.position(t);
+                throughput = throughput * medium.albedo();
+
+                let phase = |wi: Vec3| {
+                    let p = medium.phase(ray.dir, wi);
+                    (Color(p, p, p), p)
+                };
+                radiance = radiance + (throughput * self.direct_light(point, time, phase, rng));
+
+                if bounce == depth {
+                    break;
+                }
+
+                let wi = medium.sample_phase(ray.dir, rng);
+                (point, wi, medium.phase(ray.dir, wi))
+            } else {
+                let Some(hit) = closest else {
+                    let weight = match (prev_pdf, &self.environment) {
+                        (Some(pdf), Some(env)) => power_heuristic(pdf, env.pdf(ray.dir)),
+                        _ => 1.0,
+                    };
+
+                    radiance = radiance + (throughput * self.background(ray.dir) * weight);
+                    break;
+                };
+
+                let Object::Sphere(obj) = hit.obj;
+                let obj = obj.at_time(ray.time);
+                let point = ray.position(hit.t);
+                let material = obj.material_at(point);
+                let wo = -ray.dir;
+
+                let mut normal = obj.shading_normal_at(point);
+                if normal * wo < 0.0 {
+                    normal = -normal;
+                }
+                let origin = point + (normal * SURFACE_EPSILON);
+
+                let bsdf = |wi: Vec3| {
+                    let cos_theta = wi * normal;
+                    if cos_theta <= 0.0 {
+                        (Color::BLACK, 0.0)
+                    } else {
+                        (
+                            material.bsdf(normal, wo, wi) * cos_theta,
+                            material.bsdf_pdf(normal, wo, wi),
+                        )
+                    }
+                };
+                radiance = radiance + (throughput * self.direct_light(origin, time, bsdf, rng));
+
+                if bounce == depth {
+                    break;
+                }
+
+                let Some(wi) = material.sample_bsdf(normal, wo, rng) else {
+                    break;
+                };
+                let pdf = material.bsdf_pdf(normal, wo, wi);
+                if pdf <= 0.0 {
+                    break;
+                }
+
+                throughput = throughput * material.bsdf(normal, wo, wi) * ((wi * normal) / pdf);
+                (origin, wi, pdf)
+            };
+
+            if bounce >= ROULETTE_START {
+                let survival = throughput.0.max(throughput.1).max(throughput.2).min(0.95);
+                if rng.next_f32() >= survival {
+                    break;
+                }
+                throughput = throughput * (1.0 / survival);
+            }
+
+            ray = Ray::new(origin, wi).with_time(time);
+            prev_pdf = Some(pdf);
+        }
+
+        (radiance, primary)
+    }
+
+    /// Gathers light arriving at `origin` directly from the lights and environment. `scatter`
+    /// returns how much light arriving from a direction is sent along the path (including any
+    /// cosine term), and the pdf of the path having sampled that direction itself.
+    fn direct_light(
+        &self,
+        origin: Pos3,
+        time: f32,
+        scatter: impl Fn(Vec3) -> (Color, f32),
+        rng: &mut Rng,
+    ) -> Color {
+        let mut result = Color::BLACK;
+
+        for light in &self.lights {
+            let to_light = light.position - origin;
+            let dist = to_light.magnitude();
+            let wi = to_light / dist;
+
+            let (value, _) = scatter(wi);
+            if value.is_black() {
+                continue;
+            }
+
+            let tr = self.transmittance(&Ray::new(origin, wi).with_time(time), dist);
+            result = result + (value * light.intensity * tr * PI);
+        }
+
+        if let Some(sun) = self.sun() {
+            let (value, _) = scatter(sun.direction);
+            if !value.is_black() {
+                let ray = Ray::new(origin, sun.direction).with_time(time);
+                let tr = self.transmittance(&ray, f32::MAX);
+                result = result + (value * sun.intensity * tr * PI);
+            }
+        }
+
+        if let Some(env) = &self.environment {
+            let sample = env.sample(rng);
+            let (value, pdf) = scatter(sample.dir);
+
+            if sample.pdf > 0.0 && !value.is_black() {
+                let ray = Ray::new(origin, sample.dir).with_time(time);
+                let tr = self.transmittance(&ray, f32::MAX);
+                let weight = power_heuristic(sample.pdf, pdf);
+
+                result = result + (value * sample.radiance * tr * (weight / sample.pdf));
+            }
+        }
+
+        result
+    }
+}
+
+#[test]
+pub fn test_path_furnace() {
+    // a convex diffuse object lit by a uniform white environment reflects exactly its albedo
+    let scene = Scene {
+        spheres: vec![Arc::new(Sphere::new(
+            crate::identity_matrix!(),
+            Material::new(Color::WHITE, 0.0, 0.5, 0.0, 1.0),
+        ))],
+        lights: vec![],
+        environment: Some(Environment::Constant(Color::WHITE)),
+        ..Default::default()
+    };
+
+    let mut rng = Rng::new(7, 0);
+    let samples = 4000;
+    let mut total = Color::BLACK;
+
+    for _ in 0..samples {
+        let ray = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.1, 1.0));
+        total = total + scene.trace_path(ray, 4, &mut rng);
+    }
+
+    let average = total * (1.0 / samples as f32);
+    assert!((average.0 - 0.5).abs() < 0.02, "{average:?}");
+}
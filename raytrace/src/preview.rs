@@ -0,0 +1,426 @@
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    progress::CancelToken,
+    render::{tiles, Tile},
+    tonemap::ToneMapper,
+    Color, Framebuffer, Pos3, Renderer, Vec3, Viewport,
+};
+
+/// Camera moves understood by `OrbitControls`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    ZoomIn,
+    ZoomOut,
+    /// Back to where the camera started
+    Reset,
+}
+
+/// Everything the user did since the last call to `Display::input`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Input {
+    /// How far the mouse moved with the button held, in window pixels
+    pub drag: (f32, f32),
+    /// Scroll wheel movement, positive for scrolling up
+    pub scroll: f32,
+    pub keys: Vec<Key>,
+}
+
+/// Somewhere to show a render as it comes in. `MinifbDisplay` opens a real window, while tests
+/// can script the input and look at the frames instead.
+pub trait Display {
+    /// The preview ends once this returns false
+    fn is_open(&self) -> bool;
+
+    /// Shows `pixels`, packed as 0RGB in row-major order. `progress` goes from 0.0 to 1.0 as
+    /// the tiles of the current render come in.
+    fn present(&mut self, pixels: &[u32], width: usize, height: usize, progress: f32);
+
+    fn input(&mut self) -> Input;
+}
+
+/// Turns keyboard and mouse input into a camera circling a fixed target
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitControls {
+    pub target: Pos3,
+    pub distance: f32,
+    /// Around the Y axis, with 0 looking along +Z
+    pub yaw: f32,
+    /// Above the target's horizon, kept just short of straight up or down
+    pub pitch: f32,
+    start: (f32, f32, f32),
+}
+
+impl OrbitControls {
+    /// Radians per key press
+    const STEP: f32 = PI / 36.0;
+    /// Radians per pixel dragged
+    const DRAG_SPEED: f32 = 0.01;
+    /// Distance multiplier per zoom step
+    const ZOOM: f32 = 0.9;
+    const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+    /// Starts from wherever `camera` is, circling around `target`
+    pub fn new(camera: &Viewport, target: Pos3) -> Self {
+        let offset = target - camera.position;
+        let distance = offset.magnitude();
+        let (yaw, pitch) = if distance > 1e-6 {
+            let dir = offset / distance;
+            (dir.x.atan2(dir.z), dir.y.asin())
+        } else {
+            (0.0, 0.0)
+        };
+
+        let distance = if distance > 1e-6 { distance } else { 1.0 };
+        let pitch = pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+        Self {
+            target,
+            distance,
+            yaw,
+            pitch,
+            start: (distance, yaw, pitch),
+        }
+    }
+
+    /// Circles around the point straight ahead of `camera`, as far away as the origin is
+    pub fn around_view(camera: &Viewport) -> Self {
+        let distance = Vec3::from(camera.position).magnitude().max(1.0);
+        Self::new(camera, camera.position + (camera.forward() * distance))
+    }
+
+    /// Returns whether the camera moved
+    pub fn apply(&mut self, input: &Input) -> bool {
+        let before = (self.distance, self.yaw, self.pitch);
+
+        self.yaw += input.drag.0 * Self::DRAG_SPEED;
+        self.pitch += input.drag.1 * Self::DRAG_SPEED;
+        self.distance *= Self::ZOOM.powf(input.scroll);
+
+        for key in &input.keys {
+            match key {
+                Key::Left => self.yaw -= Self::STEP,
+                Key::Right => self.yaw += Self::STEP,
+                Key::Up => self.pitch += Self::STEP,
+                Key::Down => self.pitch -= Self::STEP,
+                Key::ZoomIn => self.distance *= Self::ZOOM,
+                Key::ZoomOut => self.distance /= Self::ZOOM,
+                Key::Reset => (self.distance, self.yaw, self.pitch) = self.start,
+            }
+        }
+
+        self.pitch = self.pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+        (self.distance, self.yaw, self.pitch) != before
+    }
+
+    /// Where the camera sits
+    pub fn position(&self) -> Pos3 {
+        let dir = Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+
+        self.target - (dir * self.distance)
+    }
+
+    /// `camera` moved into place and facing the target
+    pub fn camera(&self, camera: &Viewport) -> Viewport {
+        let moved = Viewport {
+            position: self.position(),
+            ..camera.clone()
+        };
+
+        moved
+            .look_at(self.target, Vec3::new(0.0, 1.0, 0.0))
+            .expect("pitch never reaches straight up or down")
+    }
+}
+
+/// Packs a color into a 0RGB pixel, tone mapped by `tone` and then encoded like
+/// `Framebuffer::to_rgb_image`
+fn pack(color: Color, tone: &ToneMapper) -> u32 {
+    let [r, g, b]: [u8; 3] = tone.apply(color).into();
+    (u32::from(r) << 16) | (u32::from(g) << 8) | u32::from(b)
+}
+
+enum Outcome {
+    Closed,
+    Moved,
+    Finished(Framebuffer),
+}
+
+/// Shows `renderer`'s image on `display` tile by tile, starting over whenever `controls` move
+/// the camera. Returns the last render that finished once the display is closed, with
+/// `renderer.camera` left wherever the controls moved it to. It comes back as rendered, `tone`
+/// only applies to what's shown.
+pub fn preview<D: Display>(
+    display: &mut D,
+    renderer: &mut Renderer,
+    controls: &mut OrbitControls,
+    tone: &ToneMapper,
+) -> Option<Framebuffer> {
+    let (width, height) = (renderer.settings.width, renderer.settings.height);
+    let tiles_total = {
+        let t = renderer.settings.tiles;
+        tiles(width, height, t.tile_size, t.order).len()
+    };
+
+    // the previous render stays on screen until new tiles cover it up
+    let mut frame = vec![0; width * height];
+    let mut finished = None;
+
+    loop {
+        renderer.camera = controls.camera(&renderer.camera);
+        let renderer: &Renderer = renderer;
+
+        let cancel = CancelToken::new();
+        let (sender, receiver) = mpsc::channel::<(Tile, Vec<u32>)>();
+
+        let outcome = thread::scope(|s| {
+            let worker = s.spawn(|| {
+                renderer.render_tiles(
+                    |tile, pixels| {
+                        let packed = pixels.iter().map(|&c| pack(c, tone)).collect();
+                        let _ = sender.send((*tile, packed));
+                    },
+                    &cancel,
+                )
+            });
+
+            let mut tiles_done = 0;
+            loop {
+                for (tile, pixels) in receiver.try_iter() {
+                    for (row, line) in pixels.chunks(tile.width).enumerate() {
+                        let start = ((tile.y + row) * width) + tile.x;
+                        frame[start..start + tile.width].copy_from_slice(line);
+                    }
+                    tiles_done += 1;
+                }
+
+                // the last tile is sent just before the render returns
+                if tiles_done == tiles_total {
+                    let image = worker.join().expect("render thread panicked");
+                    return Outcome::Finished(image.expect("only cancelled on the way out"));
+                }
+
+                display.present(
+                    &frame,
+                    width,
+                    height,
+                    tiles_done as f32 / tiles_total as f32,
+                );
+
+                if !display.is_open() {
+                    cancel.cancel();
+                    return Outcome::Closed;
+                }
+                if controls.apply(&display.input()) {
+                    cancel.cancel();
+                    return Outcome::Moved;
+                }
+            }
+        });
+
+        match outcome {
+            Outcome::Closed => return finished,
+            Outcome::Moved => continue,
+            Outcome::Finished(image) => finished = Some(image),
+        }
+
+        // nothing left to render until the camera moves
+        loop {
+            display.present(&frame, width, height, 1.0);
+            if !display.is_open() {
+                return finished;
+            }
+            if controls.apply(&display.input()) {
+                break;
+            }
+        }
+    }
+}
+
+/// A `Display` backed by a `minifb` window. Drag with the left mouse button or use the arrow
+/// keys (or WASD) to orbit, the scroll wheel or +/- to zoom, R to reset and Escape to close.
+pub struct MinifbDisplay {
+    window: minifb::Window,
+    title: String,
+    last_mouse: Option<(f32, f32)>,
+}
+
+impl MinifbDisplay {
+    pub fn new(title: &str, width: usize, height: usize) -> Result<Self, minifb::Error> {
+        let mut window =
+            minifb::Window::new(title, width, height, minifb::WindowOptions::default())?;
+        window.limit_update_rate(Some(Duration::from_micros(16_600)));
+
+        Ok(Self {
+            window,
+            title: title.to_string(),
+            last_mouse: None,
+        })
+    }
+}
+
+impl Display for MinifbDisplay {
+    fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(minifb::Key::Escape)
+    }
+
+    fn present(&mut self, pixels: &[u32], width: usize, height: usize, progress: f32) {
+        let title = if progress < 1.0 {
+            format!("{} - {:.0}%", self.title, progress * 100.0)
+        } else {
+            self.title.clone()
+        };
+        self.window.set_title(&title);
+
+        // a failed update shows up as the window closing
+        let _ = self.window.update_with_buffer(pixels, width, height);
+    }
+
+    fn input(&mut self) -> Input {
+        use minifb::{Key as K, KeyRepeat, MouseButton, MouseMode};
+
+        let mouse = self.window.get_mouse_pos(MouseMode::Pass);
+        let held = self.window.get_mouse_down(MouseButton::Left);
+        let drag = match (held, self.last_mouse, mouse) {
+            (true, Some((x0, y0)), Some((x1, y1))) => (x1 - x0, y1 - y0),
+            _ => (0.0, 0.0),
+        };
+        self.last_mouse = if held { mouse } else { None };
+
+        let keys = self
+            .window
+            .get_keys_pressed(KeyRepeat::Yes)
+            .into_iter()
+            .filter_map(|key| match key {
+                K::Left | K::A => Some(Key::Left),
+                K::Right | K::D => Some(Key::Right),
+                K::Up | K::W => Some(Key::Up),
+                K::Down | K::S => Some(Key::Down),
+                K::Equal | K::NumPadPlus => Some(Key::ZoomIn),
+                K::Minus | K::NumPadMinus => Some(Key::ZoomOut),
+                K::R => Some(Key::Reset),
+                _ => None,
+            })
+            .collect();
+
+        Input {
+            drag,
+            scroll: self.window.get_scroll_wheel().map_or(0.0, |(_, y)| y),
+            keys,
+        }
+    }
+}
+
+/// Plays back scripted input, handing out the next one each time a render completes, and
+/// closes once it runs out
+#[cfg(test)]
+struct FakeDisplay {
+    inputs: std::collections::VecDeque<Input>,
+    /// Every frame shown once its render was complete
+    finished: Vec<Vec<u32>>,
+    complete: bool,
+}
+
+#[cfg(test)]
+impl Display for FakeDisplay {
+    fn is_open(&self) -> bool {
+        !(self.complete && self.inputs.is_empty())
+    }
+
+    fn present(&mut self, pixels: &[u32], _: usize, _: usize, progress: f32) {
+        self.complete = progress >= 1.0;
+        if self.complete {
+            self.finished.push(pixels.to_vec());
+        }
+    }
+
+    fn input(&mut self) -> Input {
+        if !self.complete {
+            return Input::default();
+        }
+
+        self.inputs.pop_front().unwrap_or_default()
+    }
+}
+
+#[test]
+pub fn test_preview_orbit() {
+    use crate::{
+        identity_matrix,
+        objects::{material::Material, Sphere},
+        render::RenderSettings,
+        PointLight, Scene,
+    };
+
+    let camera = Viewport::new(Pos3::new(0.0, 0.0, -5.0), 1.0, 1.0);
+    let mut controls = OrbitControls::around_view(&camera);
+    assert_eq!(controls.target, Pos3::new(0.0, 0.0, 0.0));
+    assert_eq!(controls.position(), camera.position);
+
+    // a quarter turn to the right puts the camera on the -X side, looking along +X
+    let mut turned = controls.clone();
+    let right = Input {
+        keys: vec![Key::Right; 18],
+        ..Default::default()
+    };
+    assert!(turned.apply(&right));
+    assert_eq!(turned.position(), Pos3::new(-5.0, 0.0, 0.0));
+    assert_eq!(turned.camera(&camera).forward(), Vec3::new(1.0, 0.0, 0.0));
+    assert!(!turned.apply(&Input::default()));
+
+    let scene = Scene {
+        spheres: vec![std::sync::Arc::new(Sphere::new(
+            identity_matrix!(),
+            Material::new(Color(1.0, 0.2, 1.0), 0.1, 0.9, 0.9, 200.0),
+        ))],
+        lights: vec![PointLight::new(
+            Pos3::new(-10.0, 10.0, -10.0),
+            Color(1.0, 1.0, 1.0),
+        )],
+        ..Default::default()
+    };
+    let settings = RenderSettings {
+        width: 24,
+        height: 16,
+        ..Default::default()
+    };
+    let mut renderer = Renderer::new(scene, camera.clone(), settings);
+
+    let mut display = FakeDisplay {
+        inputs: [right.clone(), Input::default()].into(),
+        finished: Vec::new(),
+        complete: false,
+    };
+    let tone = ToneMapper::new(crate::tonemap::ToneMap::Aces, 0.5);
+    let image = preview(&mut display, &mut renderer, &mut controls, &tone).unwrap();
+
+    // what was shown matches a plain render from each camera position
+    let expected = |camera: Viewport| {
+        let renderer = Renderer::new(renderer.scene.clone(), camera, settings);
+        renderer.render()
+    };
+    let before = expected(camera.clone());
+    let after = expected(turned.camera(&camera));
+    assert_eq!(image, after);
+
+    // shown tone mapped, but returned as rendered
+    let packed = |image: &Framebuffer| {
+        let pixels = image.pixels().iter();
+        pixels.map(|&c| pack(c, &tone)).collect::<Vec<_>>()
+    };
+    assert_eq!(display.finished.first(), Some(&packed(&before)));
+    assert_eq!(display.finished.last(), Some(&packed(&after)));
+    assert_eq!(renderer.camera.position, Pos3::new(-5.0, 0.0, 0.0));
+}
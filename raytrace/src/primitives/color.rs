@@ -13,6 +13,16 @@ impl Color {
     pub const BLUE: Color = Color(0.0, 0.0, 1.0);
     pub const YELLOW: Color = Color(1.0, 1.0, 0.0);
     pub const CYAN: Color = Color(0.0, 1.0, 1.0);
+
+    /// Unlike `==`, this has no tolerance, so very dim colors aren't treated as black
+    pub fn is_black(&self) -> bool {
+        self.0 <= 0.0 && self.1 <= 0.0 && self.2 <= 0.0
+    }
+
+    /// Relative luminance using the Rec. 709 weights
+    pub fn luminance(&self) -> f32 {
+        (0.2126 * self.0) + (0.7152 * self.1) + (0.0722 * self.2)
+    }
 }
 
 impl ops::Mul<f32> for Color {
@@ -63,6 +73,37 @@ impl ops::Sub for Color {
     }
 }
 
+/// Converts a linear channel value in [0, 1] to the sRGB transfer curve
+pub fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        (1.055 * linear.powf(1.0 / 2.4)) - 0.055
+    }
+}
+
+/// Converts an sRGB-encoded channel value in [0, 1] back to linear
+pub fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl Color {
+    /// Encodes a linear color with the sRGB transfer curve
+    pub fn to_srgb(&self) -> Color {
+        Color(srgb_encode(self.0), srgb_encode(self.1), srgb_encode(self.2))
+    }
+
+    /// Decodes an sRGB-encoded color back to linear
+    pub fn to_linear(&self) -> Color {
+        Color(srgb_decode(self.0), srgb_decode(self.1), srgb_decode(self.2))
+    }
+}
+
+/// Treats the bytes as sRGB encoded, as they are in most image files
 impl From<[u8; 3]> for Color {
     fn from(value: [u8; 3]) -> Self {
         Color(
@@ -70,16 +111,17 @@ impl From<[u8; 3]> for Color {
             value[1] as f32 / 255.0,
             value[2] as f32 / 255.0,
         )
+        .to_linear()
     }
 }
 
+/// Clamps to [0, 1] and applies the sRGB transfer curve. Tone map first to keep highlights, see
+/// `Framebuffer::tone_mapped`.
 impl From<Color> for [u8; 3] {
     fn from(value: Color) -> Self {
-        [
-            (value.0 * 255.0).min(255.0) as u8,
-            (value.1 * 255.0).min(255.0) as u8,
-            (value.2 * 255.0).min(255.0) as u8,
-        ]
+        let encode = |c: f32| (srgb_encode(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
+
+        [encode(value.0), encode(value.1), encode(value.2)]
     }
 }
 
@@ -87,4 +129,16 @@ impl PartialEq for Color {
     fn eq(&self, other: &Self) -> bool {
         float_eq(self.0, other.0) && float_eq(self.1, other.1) && float_eq(self.2, other.2)
     }
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn test_color_srgb() {
+    assert_eq!(<[u8; 3]>::from(Color(0.5, 0.0, 1.0)), [188, 0, 255]);
+    // out of range values clamp instead of wrapping
+    assert_eq!(<[u8; 3]>::from(Color(1.9, -0.5, 0.0)), [255, 0, 0]);
+
+    for i in 0..=255 {
+        let bytes = [i, i, i];
+        assert_eq!(<[u8; 3]>::from(Color::from(bytes)), bytes);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::Vec3;
+
+/// An orthonormal basis around a surface normal. Local coordinates map `x` to the tangent, `y` to
+/// the bitangent and `z` to the normal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TangentFrame {
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub normal: Vec3,
+}
+
+impl TangentFrame {
+    /// `tangent` doesn't need to be exactly perpendicular to `normal`, it is re-orthogonalized
+    /// against it. Both are expected to be normalized.
+    pub fn new(normal: Vec3, tangent: Vec3) -> Self {
+        let tangent = (tangent - (normal * (normal * tangent))).to_normalized();
+        let bitangent = tangent.cross_product(normal);
+
+        Self {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+
+    /// Builds a frame with an arbitrary but consistent tangent, for when only the normal matters
+    pub fn from_normal(normal: Vec3) -> Self {
+        let helper = if normal.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+
+        Self::new(normal, helper)
+    }
+
+    pub fn to_world(&self, local: Vec3) -> Vec3 {
+        (self.tangent * local.x) + (self.bitangent * local.y) + (self.normal * local.z)
+    }
+
+    pub fn to_local(&self, world: Vec3) -> Vec3 {
+        Vec3::new(
+            world * self.tangent,
+            world * self.bitangent,
+            world * self.normal,
+        )
+    }
+}
+
+#[test]
+pub fn test_frame_roundtrip() {
+    let frame = TangentFrame::new(
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(1.0, 0.2, 0.0).to_normalized(),
+    );
+
+    assert_eq!(frame.tangent, Vec3::new(1.0, 0.2, 0.0).to_normalized());
+    assert_eq!(frame.to_world(Vec3::new(0.0, 0.0, 1.0)), frame.normal);
+
+    let v = Vec3::new(0.3, -0.5, 0.8);
+    assert_eq!(frame.to_world(frame.to_local(v)), v);
+}
@@ -0,0 +1,293 @@
+use std::ops;
+
+use crate::{float_eq, Matrix, Pos3, Vec3};
+
+/// A 4x4 transform stored inline, so it can be copied around and applied to every ray without
+/// allocating. `Matrix` is still there for other sizes.
+#[derive(Debug, Clone, Copy)]
+pub struct Mat4(pub [[f32; 4]; 4]);
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub fn new(rows: [[f32; 4]; 4]) -> Self {
+        Self(rows)
+    }
+
+    pub fn translation(x: f32, y: f32, z: f32) -> Self {
+        Self([
+            [1.0, 0.0, 0.0, x],
+            [0.0, 1.0, 0.0, y],
+            [0.0, 0.0, 1.0, z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(x: f32, y: f32, z: f32) -> Self {
+        Self([
+            [x, 0.0, 0.0, 0.0],
+            [0.0, y, 0.0, 0.0],
+            [0.0, 0.0, z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_x(rads: f32) -> Self {
+        let (sin, cos) = rads.sin_cos();
+        Self([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, -sin, 0.0],
+            [0.0, sin, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_y(rads: f32) -> Self {
+        let (sin, cos) = rads.sin_cos();
+        Self([
+            [cos, 0.0, sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin, 0.0, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_z(rads: f32) -> Self {
+        let (sin, cos) = rads.sin_cos();
+        Self([
+            [cos, -sin, 0.0, 0.0],
+            [sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Stretches the object, see `Matrix::skew`
+    pub fn skew(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+        Self([
+            [1.0, xy, xz, 0.0],
+            [yx, 1.0, yz, 0.0],
+            [zx, zy, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transposed(&self) -> Mat4 {
+        Self(std::array::from_fn(|r| {
+            std::array::from_fn(|c| self.0[c][r])
+        }))
+    }
+
+    /// The 2x2 determinants of the top and bottom pairs of rows, for every pair of columns. Every
+    /// cofactor is built from these, which saves working out the 3x3 minors one by one.
+    fn pair_determinants(&self) -> ([f32; 6], [f32; 6]) {
+        let m = &self.0;
+        let det = |r: usize, a: usize, b: usize| (m[r][a] * m[r + 1][b]) - (m[r + 1][a] * m[r][b]);
+        let pairs = [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)];
+
+        (
+            pairs.map(|(a, b)| det(0, a, b)),
+            pairs.map(|(a, b)| det(2, a, b)),
+        )
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.pair_determinants();
+        expand(&s, &c)
+    }
+
+    /// Returns an inverted matrix if an inversion is possible, otherwise returns None
+    pub fn inverted(&self) -> Option<Mat4> {
+        let (s, c) = self.pair_determinants();
+        let det = expand(&s, &c);
+        if det == 0.0 {
+            return None;
+        }
+
+        let m = &self.0;
+        let inverse = [
+            [
+                (m[1][1] * c[5]) - (m[1][2] * c[4]) + (m[1][3] * c[3]),
+                -(m[0][1] * c[5]) + (m[0][2] * c[4]) - (m[0][3] * c[3]),
+                (m[3][1] * s[5]) - (m[3][2] * s[4]) + (m[3][3] * s[3]),
+                -(m[2][1] * s[5]) + (m[2][2] * s[4]) - (m[2][3] * s[3]),
+            ],
+            [
+                -(m[1][0] * c[5]) + (m[1][2] * c[2]) - (m[1][3] * c[1]),
+                (m[0][0] * c[5]) - (m[0][2] * c[2]) + (m[0][3] * c[1]),
+                -(m[3][0] * s[5]) + (m[3][2] * s[2]) - (m[3][3] * s[1]),
+                (m[2][0] * s[5]) - (m[2][2] * s[2]) + (m[2][3] * s[1]),
+            ],
+            [
+                (m[1][0] * c[4]) - (m[1][1] * c[2]) + (m[1][3] * c[0]),
+                -(m[0][0] * c[4]) + (m[0][1] * c[2]) - (m[0][3] * c[0]),
+                (m[3][0] * s[4]) - (m[3][1] * s[2]) + (m[3][3] * s[0]),
+                -(m[2][0] * s[4]) + (m[2][1] * s[2]) - (m[2][3] * s[0]),
+            ],
+            [
+                -(m[1][0] * c[3]) + (m[1][1] * c[1]) - (m[1][2] * c[0]),
+                (m[0][0] * c[3]) - (m[0][1] * c[1]) + (m[0][2] * c[0]),
+                -(m[3][0] * s[3]) + (m[3][1] * s[1]) - (m[3][2] * s[0]),
+                (m[2][0] * s[3]) - (m[2][1] * s[1]) + (m[2][2] * s[0]),
+            ],
+        ];
+
+        Some(Self(inverse.map(|row| row.map(|x| x / det))))
+    }
+
+    /// Applies the matrix to `(x, y, z, w)`, dropping the resulting `w`
+    fn apply(&self, x: f32, y: f32, z: f32, w: f32) -> [f32; 3] {
+        std::array::from_fn(|r| {
+            let row = &self.0[r];
+            (row[0] * x) + (row[1] * y) + (row[2] * z) + (row[3] * w)
+        })
+    }
+}
+
+/// The determinant from `Mat4::pair_determinants`, by Laplace expansion along the top two rows
+fn expand(s: &[f32; 6], c: &[f32; 6]) -> f32 {
+    (s[0] * c[5]) - (s[1] * c[4]) + (s[2] * c[3]) + (s[3] * c[2]) - (s[4] * c[1]) + (s[5] * c[0])
+}
+
+impl From<Mat4> for Matrix {
+    fn from(value: Mat4) -> Self {
+        Matrix::from_vec(value.0.iter().map(|row| row.to_vec()).collect())
+    }
+}
+
+impl ops::Index<usize> for Mat4 {
+    type Output = [f32; 4];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl ops::IndexMut<usize> for Mat4 {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl PartialEq for Mat4 {
+    fn eq(&self, other: &Self) -> bool {
+        self.0
+            .iter()
+            .flatten()
+            .zip(other.0.iter().flatten())
+            .all(|(a, b)| float_eq(*a, *b))
+    }
+}
+
+impl ops::Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum())
+        }))
+    }
+}
+
+impl ops::Mul<Pos3> for &Mat4 {
+    type Output = Pos3;
+
+    fn mul(self, rhs: Pos3) -> Self::Output {
+        let [x, y, z] = self.apply(rhs.x, rhs.y, rhs.z, 1.0);
+        Pos3::new(x, y, z)
+    }
+}
+
+impl ops::Mul<Vec3> for &Mat4 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        let [x, y, z] = self.apply(rhs.x, rhs.y, rhs.z, 0.0);
+        Vec3::new(x, y, z)
+    }
+}
+
+impl ops::Mul<Pos3> for Mat4 {
+    type Output = Pos3;
+
+    fn mul(self, rhs: Pos3) -> Self::Output {
+        &self * rhs
+    }
+}
+
+impl ops::Mul<Vec3> for Mat4 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        &self * rhs
+    }
+}
+
+#[test]
+pub fn test_mat4_matches_matrix() {
+    let pairs = [
+        (
+            Mat4::translation(5.0, -3.0, 2.0),
+            Matrix::translation(5.0, -3.0, 2.0),
+        ),
+        (
+            Mat4::scaling(2.0, 3.0, -4.0),
+            Matrix::scaling(2.0, 3.0, -4.0),
+        ),
+        (Mat4::rotation_x(0.4), Matrix::rotation_x(0.4)),
+        (Mat4::rotation_y(-1.3), Matrix::rotation_y(-1.3)),
+        (Mat4::rotation_z(2.2), Matrix::rotation_z(2.2)),
+        (
+            Mat4::skew(1.0, 0.5, 0.0, 2.0, 0.25, 0.0),
+            Matrix::skew(1.0, 0.5, 0.0, 2.0, 0.25, 0.0),
+        ),
+    ];
+
+    let point = Pos3::new(-3.0, 4.0, 5.0);
+    let vector = Vec3::new(1.0, -2.0, 0.5);
+    for (mat4, matrix) in &pairs {
+        assert_eq!(Matrix::from(*mat4), *matrix);
+        assert_eq!(mat4 * point, matrix * point);
+        assert_eq!(mat4 * vector, matrix * vector);
+        assert_eq!(Matrix::from(mat4.transposed()), matrix.transposed());
+        assert!(float_eq(mat4.determinant(), matrix.get_determinant()));
+        assert_eq!(
+            Matrix::from(mat4.inverted().unwrap()),
+            matrix.inverted().unwrap()
+        );
+    }
+
+    // something with every entry filled in, from the book's inversion examples
+    let full = Mat4::new([
+        [-5.0, 2.0, 6.0, -8.0],
+        [1.0, -5.0, 1.0, 8.0],
+        [7.0, 7.0, -6.0, -7.0],
+        [1.0, -3.0, 7.0, 4.0],
+    ]);
+    let matrix = Matrix::from(full);
+    assert!(float_eq(full.determinant(), matrix.get_determinant()));
+    assert_eq!(
+        Matrix::from(full.inverted().unwrap()),
+        matrix.inverted().unwrap()
+    );
+    assert_eq!(full * full.inverted().unwrap(), Mat4::IDENTITY);
+
+    let product = pairs.iter().fold(Mat4::IDENTITY, |acc, (m, _)| acc * *m);
+    let expected = pairs
+        .iter()
+        .fold(Matrix::from(Mat4::IDENTITY), |acc, (_, m)| acc * m.clone());
+    assert_eq!(Matrix::from(product), expected);
+
+    assert!(Mat4::scaling(0.0, 1.0, 1.0).inverted().is_none());
+}
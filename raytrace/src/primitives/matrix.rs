@@ -96,9 +96,9 @@ impl Matrix {
     pub fn get_cols(&self) -> Vec<Vec<f32>> {
         let mut result = vec![vec![0.0; self.height]; self.width];
 
-        for i in 0..self.width {
-            for j in 0..self.height {
-                result[i][j] = self.inner[j][i]
+        for (i, col) in result.iter_mut().enumerate() {
+            for (j, x) in col.iter_mut().enumerate() {
+                *x = self.inner[j][i]
             }
         }
 
@@ -125,16 +125,16 @@ impl Matrix {
                 vec![0.0, 0.0, 1.0, 0.0],
                 vec![0.0, 0.0, 0.0, 1.0],
             ]),
-            (c, r) => panic!("shouldn't ever need to handle matricies larger than 4x4"),
+            _ => panic!("shouldn't ever need to handle matricies larger than 4x4"),
         }
     }
 
     pub fn transposed(&self) -> Matrix {
-        let mut result = Matrix::new(self.width, self.height);
+        let mut result = Matrix::new(self.height, self.width);
 
-        for i in 0..self.width {
-            for j in 0..self.height {
-                result[i][j] = self.inner[j][i]
+        for (i, row) in result.inner.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.inner[j][i]
             }
         }
 
@@ -144,7 +144,7 @@ impl Matrix {
     pub fn get_determinant(&self) -> f32 {
         match (self.height, self.width) {
             (2, 2) => (self.inner[0][0] * self.inner[1][1]) - (self.inner[0][1] * self.inner[1][0]),
-            (r, c) => self[0]
+            _ => self[0]
                 .iter()
                 .enumerate()
                 .map(|(i, x)| *x * self.cofactor(0, i))
@@ -169,7 +169,7 @@ impl Matrix {
 
     pub fn cofactor(&self, row: usize, column: usize) -> f32 {
         let result = self.minor(row, column);
-        if (row + column) % 2 == 0 {
+        if (row + column).is_multiple_of(2) {
             result
         } else {
             -result
@@ -0,0 +1,149 @@
+use std::ops;
+
+use crate::{Mat4, Vec3};
+
+/// A rotation stored as a unit quaternion, `w + xi + yj + zk`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// Turns `rads` around `axis`, in the same direction as `Mat4::rotation_x` and friends
+    pub fn from_axis_angle(axis: Vec3, rads: f32) -> Self {
+        let axis = axis.to_normalized();
+        let (sin, cos) = (rads / 2.0).sin_cos();
+        Self::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    /// Rotates around X, then Y, then Z, like chaining `rotate-x`, `rotate-y` and `rotate-z`
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        let qx = Self::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), x);
+        let qy = Self::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), y);
+        let qz = Self::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), z);
+        qz * qy * qx
+    }
+
+    pub fn dot(&self, rhs: Quat) -> f32 {
+        (self.w * rhs.w) + (self.x * rhs.x) + (self.y * rhs.y) + (self.z * rhs.z)
+    }
+
+    pub fn normalized(&self) -> Quat {
+        let len = self.dot(*self).sqrt();
+        Self::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    /// Spherical interpolation, turning at a constant rate the short way round from `self` at
+    /// `t = 0.0` to `rhs` at `t = 1.0`
+    pub fn slerp(&self, rhs: Quat, t: f32) -> Quat {
+        // q and -q are the same rotation, pick whichever is closer
+        let (rhs, cos) = match self.dot(rhs) {
+            cos if cos < 0.0 => (Self::new(-rhs.w, -rhs.x, -rhs.y, -rhs.z), -cos),
+            cos => (rhs, cos),
+        };
+
+        let (a, b) = if cos > 0.9995 {
+            // nearly the same rotation, where lerping is accurate and sin(angle) isn't
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+
+        Self::new(
+            (self.w * a) + (rhs.w * b),
+            (self.x * a) + (rhs.x * b),
+            (self.y * a) + (rhs.y * b),
+            (self.z * a) + (rhs.z * b),
+        )
+        .normalized()
+    }
+
+    pub fn to_matrix(&self) -> Mat4 {
+        let Quat { w, x, y, z } = self.normalized();
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl ops::Mul for Quat {
+    type Output = Quat;
+
+    /// The rotation `rhs` followed by `self`
+    fn mul(self, rhs: Quat) -> Self::Output {
+        let (a, b) = (self, rhs);
+        Quat::new(
+            (a.w * b.w) - (a.x * b.x) - (a.y * b.y) - (a.z * b.z),
+            (a.w * b.x) + (a.x * b.w) + (a.y * b.z) - (a.z * b.y),
+            (a.w * b.y) - (a.x * b.z) + (a.y * b.w) + (a.z * b.x),
+            (a.w * b.z) + (a.x * b.y) - (a.y * b.x) + (a.z * b.w),
+        )
+    }
+}
+
+#[test]
+pub fn test_quat_rotation() {
+    use std::f32::consts::PI;
+
+    let q = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 0.7);
+    assert_eq!(q.to_matrix(), Mat4::rotation_y(0.7));
+
+    let euler = Quat::from_euler(0.3, -1.1, 2.0);
+    assert_eq!(
+        euler.to_matrix(),
+        Mat4::rotation_z(2.0) * (Mat4::rotation_y(-1.1) * Mat4::rotation_x(0.3))
+    );
+
+    // halfway through a quarter turn is an eighth of a turn
+    let end = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), PI / 2.0);
+    let half = Quat::IDENTITY.slerp(end, 0.5);
+    assert_eq!(half.to_matrix(), Mat4::rotation_z(PI / 4.0));
+    assert_eq!(Quat::IDENTITY.slerp(end, 1.0).to_matrix(), end.to_matrix());
+
+    // the short way round, even when the two ends have opposite signs
+    let flipped = Quat::new(-end.w, -end.x, -end.y, -end.z);
+    assert_eq!(
+        Quat::IDENTITY.slerp(flipped, 0.5).to_matrix(),
+        Mat4::rotation_z(PI / 4.0)
+    );
+}
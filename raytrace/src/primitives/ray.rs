@@ -1,35 +1,51 @@
 use std::sync::Arc;
 
-use crate::{
-    objects::{material::Material, Sphere},
-    scene::Intersection,
-    Matrix, Object, Pos3, Vec3,
-};
+use crate::{objects::Sphere, scene::Intersection, Mat4, Object, Pos3, Vec3};
+
+#[cfg(test)]
+use crate::objects::material::Material;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Ray {
     pub origin: Pos3,
     pub dir: Vec3,
+    /// When the ray was sent, in seconds of animation time. Moving spheres are hit wherever they
+    /// are at this moment.
+    pub time: f32,
 }
 
 impl Ray {
     pub fn new(origin: Pos3, dir: Vec3) -> Self {
-        Self { origin, dir }
+        Self {
+            origin,
+            dir,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
     }
 
     pub fn position(&self, time: f32) -> Pos3 {
         self.origin + (self.dir * time)
     }
 
-    pub fn transform(&self, matrix: &Matrix) -> Self {
+    pub fn transform(&self, matrix: &Mat4) -> Self {
         Self {
             origin: matrix * self.origin,
             dir: matrix * self.dir,
+            time: self.time,
         }
     }
 
     pub fn sphere_intersect(&self, sphere: &Arc<Sphere>) -> Vec<Intersection> {
-        let ray_tf = self.transform(&sphere.transform.inverted().unwrap());
+        // a moving sphere is hit where it is at the ray's time, see `Sphere::at_time` for shading
+        let Some(t_inverted) = sphere.t_inverted_at(self.time) else {
+            return Vec::new();
+        };
+        let ray_tf = self.transform(&t_inverted);
         let sphr_to_ray = ray_tf.origin - Pos3::new(0.0, 0.0, 0.0);
         let a = ray_tf.dir * ray_tf.dir;
         let b = 2.0 * (sphr_to_ray * ray_tf.dir);
@@ -54,14 +70,14 @@ impl Ray {
 #[test]
 pub fn test_ray_transform() {
     let ray_1 = Ray::new(Pos3::new(1.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0));
-    let test_1 = Matrix::translation(3.0, 4.0, 5.0);
+    let test_1 = Mat4::translation(3.0, 4.0, 5.0);
 
     assert_eq!(
         ray_1.transform(&test_1),
         Ray::new(Pos3::new(4.0, 6.0, 8.0), Vec3::new(0.0, 1.0, 0.0))
     );
 
-    let test_2 = Matrix::scaling(2.0, 3.0, 4.0);
+    let test_2 = Mat4::scaling(2.0, 3.0, 4.0);
 
     assert_eq!(
         ray_1.transform(&test_2),
@@ -73,7 +89,7 @@ pub fn test_ray_transform() {
 pub fn test_ray_intersect() {
     let ray_1 = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
     let sphere = Arc::new(Sphere::new(
-        Matrix::scaling(2.0, 2.0, 2.0),
+        Mat4::scaling(2.0, 2.0, 2.0),
         Material::default(),
     ));
 
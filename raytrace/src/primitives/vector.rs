@@ -30,7 +30,7 @@ impl Vec3 {
     pub fn cross_product(&self, rhs: Vec3) -> Vec3 {
         Vec3 {
             x: (self.y * rhs.z) - (self.z * rhs.y),
-            y: (self.x * rhs.z) - (self.z * rhs.x),
+            y: (self.z * rhs.x) - (self.x * rhs.z),
             z: (self.x * rhs.y) - (self.y * rhs.x),
         }
     }
@@ -152,3 +152,12 @@ impl PartialEq for Vec3 {
         float_eq(self.x, other.x) && float_eq(self.y, other.y) && float_eq(self.z, other.z)
     }
 }
+
+#[test]
+pub fn test_cross_product() {
+    let a = Vec3::new(1.0, 2.0, 3.0);
+    let b = Vec3::new(2.0, 3.0, 4.0);
+
+    assert_eq!(a.cross_product(b), Vec3::new(-1.0, 2.0, -1.0));
+    assert_eq!(b.cross_product(a), Vec3::new(1.0, -2.0, 1.0));
+}
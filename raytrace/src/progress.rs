@@ -0,0 +1,64 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// A snapshot of how far along a render is, passed to the callback after every finished tile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    /// Camera rays traced so far. Secondary rays spawned by the integrator aren't counted.
+    pub rays: u64,
+    pub elapsed: Duration,
+    /// Extrapolated from the pixels finished so far
+    pub eta: Duration,
+}
+
+impl Progress {
+    /// From 0.0 to 1.0
+    pub fn fraction(&self) -> f32 {
+        if self.tiles_total == 0 {
+            return 1.0;
+        }
+
+        self.tiles_done as f32 / self.tiles_total as f32
+    }
+}
+
+/// Shared flag for stopping a render early. Clones all refer to the same flag, so one can be
+/// handed to the renderer while another stays with whatever decides to cancel. Workers check it
+/// before claiming each tile, so tiles already in progress still finish.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Returned by renders stopped through a `CancelToken`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "render was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
@@ -0,0 +1,399 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    progress::CancelToken, render::Integrator, sampling::Rng, Color, Framebuffer, Renderer,
+};
+
+/// Identifies checkpoint files, followed by a format version
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 2;
+/// Magic, version, width, height and `RenderKey`
+const HEADER_LEN: u64 = 4 + 4 + 8 + 8 + 8 + 4 + 8 + 4 + 8;
+/// The color sum, sample count and generator state of one pixel
+const PIXEL_LEN: u64 = (3 * 4) + 4 + 8 + 8;
+
+/// Everything besides the size that a checkpoint has to have been rendered with to be resumed.
+/// Samples from different settings or scenes would be averaged into a mix of both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RenderKey {
+    seed: u64,
+    integrator: Integrator,
+    max_depth: usize,
+    /// The sample count being worked towards, which the jitter pattern depends on
+    samples: u32,
+    /// `Renderer::scene_hash`
+    scene: u64,
+}
+
+/// FNV-1a over everything written to it. Fed through `Debug`, so every field of the scene is
+/// covered without each type implementing `Hash`, and without building the whole string.
+struct Fnv(u64);
+
+impl fmt::Write for Fnv {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+        Ok(())
+    }
+}
+
+/// Running totals for a progressive render. Every pixel keeps its own sample count and generator,
+/// so the final image doesn't depend on how many passes happened between checkpoints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    sum: Framebuffer,
+    samples: Vec<u32>,
+    rngs: Vec<Rng>,
+    key: RenderKey,
+}
+
+impl Accumulator {
+    pub fn width(&self) -> usize {
+        self.sum.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.sum.height()
+    }
+
+    /// Number of passes every pixel has completed
+    pub fn passes(&self) -> u32 {
+        self.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    /// The average of every sample so far
+    pub fn image(&self) -> Framebuffer {
+        let pixels = self
+            .sum
+            .pixels()
+            .iter()
+            .zip(&self.samples)
+            .map(|(sum, &n)| {
+                if n == 0 {
+                    *sum
+                } else {
+                    *sum * (1.0 / n as f32)
+                }
+            })
+            .collect();
+
+        Framebuffer::from_pixels(self.width(), self.height(), pixels)
+    }
+
+    /// Writes the accumulated colors, sample counts and generator states. The file is written
+    /// next to `path` first and moved into place, so an interruption never leaves a broken
+    /// checkpoint behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+
+        let mut out = BufWriter::new(File::create(&partial)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.width() as u64).to_le_bytes())?;
+        out.write_all(&(self.height() as u64).to_le_bytes())?;
+        let key = &self.key;
+        let integrator = Integrator::ALL.iter().position(|&i| i == key.integrator);
+        out.write_all(&key.seed.to_le_bytes())?;
+        out.write_all(&(integrator.unwrap_or(0) as u32).to_le_bytes())?;
+        out.write_all(&(key.max_depth as u64).to_le_bytes())?;
+        out.write_all(&key.samples.to_le_bytes())?;
+        out.write_all(&key.scene.to_le_bytes())?;
+
+        for ((sum, samples), rng) in self.sum.pixels().iter().zip(&self.samples).zip(&self.rngs) {
+            let (state, inc) = rng.state();
+            for value in [sum.0, sum.1, sum.2] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+            out.write_all(&samples.to_le_bytes())?;
+            out.write_all(&state.to_le_bytes())?;
+            out.write_all(&inc.to_le_bytes())?;
+        }
+
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(partial, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a render checkpoint"));
+        }
+        if read_u32(&mut file)? != VERSION {
+            return Err(invalid("unsupported checkpoint version"));
+        }
+
+        let width = read_u64(&mut file)?;
+        let height = read_u64(&mut file)?;
+        let seed = read_u64(&mut file)?;
+        let integrator = Integrator::ALL
+            .get(read_u32(&mut file)? as usize)
+            .copied()
+            .ok_or_else(|| invalid("unknown integrator in checkpoint"))?;
+        let key = RenderKey {
+            seed,
+            integrator,
+            max_depth: read_u64(&mut file)? as usize,
+            samples: read_u32(&mut file)?,
+            scene: read_u64(&mut file)?,
+        };
+
+        // checked before allocating anything, so a corrupt header can't ask for too much memory
+        let expected = width
+            .checked_mul(height)
+            .and_then(|count| count.checked_mul(PIXEL_LEN))
+            .and_then(|pixels| pixels.checked_add(HEADER_LEN));
+        if expected != Some(len) {
+            return Err(invalid("checkpoint size doesn't match its dimensions"));
+        }
+        let (width, height) = (width as usize, height as usize);
+        let count = width * height;
+
+        let mut pixels = Vec::with_capacity(count);
+        let mut samples = Vec::with_capacity(count);
+        let mut rngs = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let r = read_f32(&mut file)?;
+            let g = read_f32(&mut file)?;
+            let b = read_f32(&mut file)?;
+            pixels.push(Color(r, g, b));
+            samples.push(read_u32(&mut file)?);
+            rngs.push(Rng::from_state(read_u64(&mut file)?, read_u64(&mut file)?));
+        }
+
+        Ok(Self {
+            sum: Framebuffer::from_pixels(width, height, pixels),
+            samples,
+            rngs,
+            key,
+        })
+    }
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(r)?))
+}
+
+impl Renderer {
+    /// Identifies the scene and camera, so a checkpoint isn't resumed after either has changed
+    pub fn scene_hash(&self) -> u64 {
+        let mut hash = Fnv(0xcbf2_9ce4_8422_2325);
+        write!(hash, "{:?}{:?}", self.scene, self.camera).expect("hashing never fails");
+        hash.0
+    }
+
+    fn render_key(&self) -> RenderKey {
+        RenderKey {
+            seed: self.settings.seed,
+            integrator: self.settings.integrator,
+            max_depth: self.settings.max_depth,
+            samples: self.settings.samples,
+            scene: self.scene_hash(),
+        }
+    }
+
+    /// An empty accumulator matching the render settings
+    pub fn accumulator(&self) -> Accumulator {
+        let (width, height) = (self.settings.width, self.settings.height);
+
+        let rngs = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel_rng(x, y))
+            .collect();
+
+        Accumulator {
+            sum: Framebuffer::new(width, height),
+            samples: vec![0; width * height],
+            rngs,
+            key: self.render_key(),
+        }
+    }
+
+    /// Adds one more sample to every pixel. Samples are always jittered, so as long as
+    /// `settings.samples` is more than 1, the image after that many passes matches what `render`
+    /// produces.
+    pub fn render_pass(&self, acc: &mut Accumulator) {
+        let width = acc.width();
+        let current: &Accumulator = acc;
+
+        let results = self
+            .settings
+            .tiles
+            .render_with(width, current.height(), |x, y| {
+                let mut rng = current.rngs[(y * width) + x].clone();
+                let color = self.sample_pixel(x, y, &mut rng);
+                (color, rng)
+            });
+
+        for (i, (color, rng)) in results.into_iter().enumerate() {
+            let sum = &mut acc.sum.pixels_mut()[i];
+            *sum = *sum + color;
+            acc.samples[i] += 1;
+            acc.rngs[i] = rng;
+        }
+    }
+
+    /// Renders pass after pass until every pixel has `settings.samples` samples, saving a
+    /// checkpoint to `checkpoint` every `interval` passes and when stopped through `cancel`.
+    ///
+    /// If `checkpoint` already holds a render of the same scene with the same settings, it carries
+    /// on from there, and the result is the same as if it had never been interrupted. Anything
+    /// else is refused rather than mixed in. Check `Accumulator::passes` to tell whether the
+    /// render was finished or cancelled.
+    pub fn render_progressive(
+        &self,
+        checkpoint: impl AsRef<Path>,
+        interval: u32,
+        cancel: &CancelToken,
+    ) -> io::Result<Accumulator> {
+        let checkpoint = checkpoint.as_ref();
+
+        let mut acc = match Accumulator::load(checkpoint) {
+            Ok(acc) => {
+                let key = self.render_key();
+                let message = if acc.key.scene != key.scene {
+                    Some("checkpoint was made from a different scene or camera")
+                } else if acc.key != key
+                    || acc.width() != self.settings.width
+                    || acc.height() != self.settings.height
+                {
+                    Some("checkpoint was made with different render settings")
+                } else {
+                    None
+                };
+                if let Some(message) = message {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+                acc
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.accumulator(),
+            Err(e) => return Err(e),
+        };
+
+        let target = self.settings.samples.max(1);
+        let mut since_save = 0;
+        while acc.passes() < target {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            self.render_pass(&mut acc);
+            since_save += 1;
+
+            if since_save >= interval.max(1) {
+                acc.save(checkpoint)?;
+                since_save = 0;
+            }
+        }
+
+        if since_save > 0 {
+            acc.save(checkpoint)?;
+        }
+
+        Ok(acc)
+    }
+}
+
+#[test]
+pub fn test_progressive_resume() {
+    use crate::{
+        render::{Integrator, RenderSettings},
+        Viewport,
+    };
+
+    let settings = RenderSettings {
+        width: 8,
+        height: 8,
+        samples: 4,
+        integrator: Integrator::Path,
+        seed: 3,
+        ..Default::default()
+    };
+    let camera = Viewport::new(crate::Pos3::new(0.0, 0.0, -5.0), 1.0, 1.0);
+    let renderer = Renderer::new(crate::Scene::default(), camera, settings);
+
+    let name = format!("raytrace_test_progressive_{}.ckpt", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    let _ = fs::remove_file(&path);
+
+    // stop after the first pass, as if the render was interrupted
+    let cancel = CancelToken::new();
+    let mut acc = renderer.accumulator();
+    renderer.render_pass(&mut acc);
+    acc.save(&path).unwrap();
+    assert_eq!(Accumulator::load(&path).unwrap(), acc);
+
+    let resumed = renderer.render_progressive(&path, 1, &cancel).unwrap();
+    assert_eq!(resumed.passes(), 4);
+
+    // bit-for-bit the same as rendering in one go
+    let full = renderer.render();
+    assert!(resumed
+        .image()
+        .pixels()
+        .iter()
+        .zip(full.pixels())
+        .all(|(a, b)| [a.0, a.1, a.2].map(f32::to_bits) == [b.0, b.1, b.2].map(f32::to_bits)));
+
+    // anything that would change the samples is refused instead of mixed in
+    let resume = |renderer: &Renderer| renderer.render_progressive(&path, 1, &cancel).unwrap_err();
+    for settings in [
+        RenderSettings {
+            max_depth: 5,
+            ..settings
+        },
+        RenderSettings {
+            integrator: Integrator::AmbientOcclusion,
+            ..settings
+        },
+        RenderSettings {
+            samples: 8,
+            ..settings
+        },
+    ] {
+        let changed = Renderer::new(crate::Scene::default(), renderer.camera.clone(), settings);
+        assert_eq!(resume(&changed).kind(), io::ErrorKind::InvalidData);
+    }
+    let mut moved = renderer.clone();
+    moved.camera.position = crate::Pos3::new(0.0, 1.0, -5.0);
+    assert_eq!(
+        resume(&moved).to_string(),
+        "checkpoint was made from a different scene or camera"
+    );
+
+    // a header promising more pixels than the file holds is caught before allocating them
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+    assert_eq!(
+        Accumulator::load(&path).unwrap_err().to_string(),
+        "checkpoint size doesn't match its dimensions"
+    );
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,665 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    aov::{Aov, AovBuffers, AovSample, PrimaryHit},
+    center_rel,
+    progress::{CancelToken, Cancelled, Progress},
+    sampling::Rng,
+    Color, Framebuffer, Ray, Scene, Viewport,
+};
+
+/// The order tiles are handed out to workers. Doesn't affect the result, only which parts of the
+/// frame finish first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// Row by row from the top left
+    Scanline,
+    /// Outwards from the center, where the subject usually is
+    #[default]
+    Spiral,
+    /// Along a Hilbert curve, which keeps consecutive tiles next to each other for better cache
+    /// use
+    Hilbert,
+}
+
+/// A rectangle of pixels, in top-left relative coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Distance along a Hilbert curve filling an `n` by `n` grid, where `n` is a power of 2
+fn hilbert_index(n: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = usize::from(x & s > 0);
+        let ry = usize::from(y & s > 0);
+        d += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant so the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+/// Splits a `width` by `height` frame into tiles of at most `tile_size` pixels square. Tiles on
+/// the right and bottom edges are cropped to fit.
+pub fn tiles(width: usize, height: usize, tile_size: usize, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let columns = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+
+    let mut grid: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let center_x = (columns as f32 - 1.0) / 2.0;
+            let center_y = (rows as f32 - 1.0) / 2.0;
+            let key = |&(column, row): &(usize, usize)| {
+                let dx = column as f32 - center_x;
+                let dy = row as f32 - center_y;
+                // rings first, then clockwise around each ring
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let n = columns.max(rows).next_power_of_two();
+            grid.sort_by_key(|&(column, row)| hilbert_index(n, column, row));
+        }
+    }
+
+    grid.into_iter()
+        .map(|(column, row)| {
+            let x = column * tile_size;
+            let y = row * tile_size;
+            Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            }
+        })
+        .collect()
+}
+
+/// Renders a frame in parallel, one tile at a time. Workers claim tiles from a shared atomic
+/// counter and write into buffers they own, which get stitched together at the end, so nothing is
+/// locked while rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRenderer {
+    pub tile_size: usize,
+    pub order: TileOrder,
+}
+
+impl Default for TileRenderer {
+    fn default() -> Self {
+        Self {
+            tile_size: 32,
+            order: TileOrder::default(),
+        }
+    }
+}
+
+impl TileRenderer {
+    pub fn new(tile_size: usize, order: TileOrder) -> Self {
+        Self { tile_size, order }
+    }
+
+    /// Calls `shade` once for every pixel, with top-left relative coordinates
+    pub fn render<F>(&self, width: usize, height: usize, shade: F) -> Framebuffer
+    where
+        F: Fn(usize, usize) -> Color + Sync,
+    {
+        Framebuffer::from_pixels(width, height, self.render_with(width, height, shade))
+    }
+
+    /// Like `render`, for any per-pixel value. Returns them in row-major order.
+    pub fn render_with<T, F>(&self, width: usize, height: usize, shade: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Sync,
+    {
+        self.render_tracked(width, height, shade, |_, _, _, _| {}, &CancelToken::new())
+            .expect("nothing else can cancel the render")
+    }
+
+    /// Like `render_with`, calling `on_tile` with each tile as it finishes, its pixels in
+    /// row-major order, and the number of tiles done so far and in total. Returns `Err` if
+    /// `cancel` is triggered before every tile was claimed.
+    pub fn render_tracked<T, F, P>(
+        &self,
+        width: usize,
+        height: usize,
+        shade: F,
+        on_tile: P,
+        cancel: &CancelToken,
+    ) -> Result<Vec<T>, Cancelled>
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Sync,
+        P: Fn(&Tile, &[T], usize, usize) + Sync,
+    {
+        let tiles = tiles(width, height, self.tile_size, self.order);
+        let next = AtomicUsize::new(0);
+        let finished = AtomicUsize::new(0);
+
+        let rendered: Vec<(Tile, Vec<T>)> = (0..rayon::current_num_threads())
+            .into_par_iter()
+            .flat_map_iter(|_| {
+                let mut done = Vec::new();
+                loop {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(tile) = tiles.get(i) else {
+                        break;
+                    };
+
+                    let mut pixels = Vec::with_capacity(tile.width * tile.height);
+                    for y in tile.y..tile.y + tile.height {
+                        for x in tile.x..tile.x + tile.width {
+                            pixels.push(shade(x, y));
+                        }
+                    }
+                    let count = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                    on_tile(tile, &pixels, count, tiles.len());
+                    done.push((*tile, pixels));
+                }
+                done
+            })
+            .collect();
+
+        if rendered.len() < tiles.len() {
+            return Err(Cancelled);
+        }
+
+        let mut frame: Vec<Option<T>> = (0..width * height).map(|_| None).collect();
+        for (tile, pixels) in rendered {
+            for (i, pixel) in pixels.into_iter().enumerate() {
+                let (x, y) = (tile.x + (i % tile.width), tile.y + (i / tile.width));
+                frame[(y * width) + x] = Some(pixel);
+            }
+        }
+
+        Ok(frame
+            .into_iter()
+            .map(|p| p.expect("tiles cover every pixel"))
+            .collect())
+    }
+}
+
+/// How the color of each camera ray is worked out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    /// `Scene::trace_ray`, direct lighting only
+    #[default]
+    Whitted,
+    /// `Scene::trace_path`
+    Path,
+    /// `Scene::trace_ao`, using `Scene::ao` or the default settings
+    AmbientOcclusion,
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 3] = [
+        Integrator::Whitted,
+        Integrator::Path,
+        Integrator::AmbientOcclusion,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::Whitted => "whitted",
+            Integrator::Path => "path",
+            Integrator::AmbientOcclusion => "ao",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Integrator> {
+        Integrator::ALL.into_iter().find(|i| i.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    /// Samples per pixel. With more than one, each is jittered within the pixel.
+    pub samples: u32,
+    /// Bounces for the path tracer, or the recursion depth for `trace_ray`
+    pub max_depth: usize,
+    pub integrator: Integrator,
+    pub tiles: TileRenderer,
+    /// Renders with the same seed and settings produce the same image
+    pub seed: u64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 1000,
+            height: 1000,
+            samples: 1,
+            max_depth: 3,
+            integrator: Integrator::default(),
+            tiles: TileRenderer::default(),
+            seed: 0,
+        }
+    }
+}
+
+/// Turns a `Scene` into a `Framebuffer`, as seen from `camera`
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    pub scene: Scene,
+    pub camera: Viewport,
+    pub settings: RenderSettings,
+}
+
+impl Renderer {
+    /// Camera rays from `trace_ray` start on the viewport plane, one unit in front of the camera
+    const NEAR_PLANE: f32 = 1.0;
+
+    pub fn new(scene: Scene, camera: Viewport, settings: RenderSettings) -> Self {
+        Self {
+            scene,
+            camera,
+            settings,
+        }
+    }
+
+    /// The camera ray through a point in the pixel at top-left relative (x, y), where (0.0, 0.0)
+    /// is the pixel's own sample position
+    pub fn camera_ray(&self, x: usize, y: usize, dx: f32, dy: f32) -> Ray {
+        let RenderSettings { width, height, .. } = self.settings;
+        let (cx, cy) = center_rel(width, height, x, y);
+
+        self.camera
+            .ray_through(cx as f32 + dx, cy as f32 - dy, width, height)
+    }
+
+    /// The generator used for every sample of one pixel
+    pub(crate) fn pixel_rng(&self, x: usize, y: usize) -> Rng {
+        Rng::new(self.settings.seed, ((y * self.settings.width) + x) as u64)
+    }
+
+    /// Traces a single camera ray with the configured integrator
+    fn trace(&self, ray: Ray, rng: &mut Rng) -> (Color, PrimaryHit) {
+        match self.settings.integrator {
+            Integrator::Whitted => {
+                self.scene
+                    .trace_ray_hit(ray, Self::NEAR_PLANE, f32::MAX, self.settings.max_depth)
+            }
+            Integrator::Path => self.scene.trace_path_hit(ray, self.settings.max_depth, rng),
+            Integrator::AmbientOcclusion => {
+                let settings = self.scene.ao.unwrap_or_default();
+                self.scene.trace_ao_hit(ray, &settings, rng)
+            }
+        }
+    }
+
+    /// Averages `samples` rays through the pixel at top-left relative (x, y)
+    pub fn render_pixel(&self, x: usize, y: usize) -> Color {
+        self.shade_pixel(x, y, false).0
+    }
+
+    /// Like `render_pixel`, also reading the variables from the first of its rays. They all come
+    /// from that one hit, so they line up with each other, and with one sample per pixel that
+    /// ray goes through the pixel's center.
+    pub fn render_pixel_aov(&self, x: usize, y: usize) -> (Color, AovSample) {
+        let (color, sample) = self.shade_pixel(x, y, true);
+        (color, sample.expect("variables were asked for"))
+    }
+
+    fn shade_pixel(&self, x: usize, y: usize, aovs: bool) -> (Color, Option<AovSample>) {
+        let mut rng = self.pixel_rng(x, y);
+        let samples = self.settings.samples.max(1);
+        if samples == 1 {
+            let (color, primary) = self.trace(self.camera_ray(x, y, 0.0, 0.0), &mut rng);
+            return (color, aovs.then(|| self.scene.aov_sample(&primary)));
+        }
+
+        let mut total = Color::BLACK;
+        let mut sample = None;
+        for i in 0..samples {
+            let ray = self.sample_ray(x, y, &mut rng);
+            let (color, primary) = self.trace(ray, &mut rng);
+            total = total + color;
+
+            if aovs && i == 0 {
+                sample = Some(self.scene.aov_sample(&primary));
+            }
+        }
+
+        (total * (1.0 / samples as f32), sample)
+    }
+
+    /// A ray through a random point in the pixel, at a random moment while the shutter is open
+    fn sample_ray(&self, x: usize, y: usize, rng: &mut Rng) -> Ray {
+        let (dx, dy) = (rng.next_f32() - 0.5, rng.next_f32() - 0.5);
+        let mut ray = self.camera_ray(x, y, dx, dy);
+        if self.camera.has_motion_blur() {
+            let (open, close) = self.camera.shutter;
+            ray.time = open + (rng.next_f32() * (close - open));
+        }
+        ray
+    }
+
+    /// Traces one ray from `sample_ray`
+    pub(crate) fn sample_pixel(&self, x: usize, y: usize, rng: &mut Rng) -> Color {
+        let ray = self.sample_ray(x, y, rng);
+        self.trace(ray, rng).0
+    }
+
+    pub fn render(&self) -> Framebuffer {
+        let RenderSettings { width, height, .. } = self.settings;
+        self.settings
+            .tiles
+            .render(width, height, |x, y| self.render_pixel(x, y))
+    }
+
+    /// Like `render`, calling `on_progress` from the worker threads every time a tile finishes,
+    /// and stopping early once `cancel` is triggered
+    pub fn render_with_progress<P>(
+        &self,
+        on_progress: P,
+        cancel: &CancelToken,
+    ) -> Result<Framebuffer, Cancelled>
+    where
+        P: Fn(&Progress) + Sync,
+    {
+        let RenderSettings { width, height, .. } = self.settings;
+        let pixels = self.render_tracked(|x, y| self.render_pixel(x, y), on_progress, cancel)?;
+
+        Ok(Framebuffer::from_pixels(width, height, pixels))
+    }
+
+    /// Like `render_with_progress`, also filling the requested variables from the same camera
+    /// rays, see `render_pixel_aov`
+    pub fn render_with_aovs<P>(
+        &self,
+        aovs: &[Aov],
+        on_progress: P,
+        cancel: &CancelToken,
+    ) -> Result<(Framebuffer, AovBuffers), Cancelled>
+    where
+        P: Fn(&Progress) + Sync,
+    {
+        let RenderSettings { width, height, .. } = self.settings;
+        let pixels =
+            self.render_tracked(|x, y| self.render_pixel_aov(x, y), on_progress, cancel)?;
+
+        let mut buffers = AovBuffers::new(width, height, aovs);
+        let mut colors = Vec::with_capacity(pixels.len());
+        for (i, (color, sample)) in pixels.into_iter().enumerate() {
+            buffers.set(i % width, i / width, &sample);
+            colors.push(color);
+        }
+
+        Ok((Framebuffer::from_pixels(width, height, colors), buffers))
+    }
+
+    /// Renders every pixel with `shade`, reporting progress as tiles finish
+    fn render_tracked<T, F, P>(
+        &self,
+        shade: F,
+        on_progress: P,
+        cancel: &CancelToken,
+    ) -> Result<Vec<T>, Cancelled>
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Sync,
+        P: Fn(&Progress) + Sync,
+    {
+        let RenderSettings {
+            width,
+            height,
+            samples,
+            ..
+        } = self.settings;
+        let start = Instant::now();
+        let pixels_done = AtomicU64::new(0);
+        let pixels_total = (width * height) as u64;
+
+        let on_tile = |tile: &Tile, _: &[T], tiles_done: usize, tiles_total: usize| {
+            let area = (tile.width * tile.height) as u64;
+            let pixels = pixels_done.fetch_add(area, Ordering::Relaxed) + area;
+            let elapsed = start.elapsed();
+            let remaining = pixels_total.saturating_sub(pixels) as f64 / pixels.max(1) as f64;
+
+            on_progress(&Progress {
+                tiles_done,
+                tiles_total,
+                rays: pixels * samples.max(1) as u64,
+                elapsed,
+                eta: elapsed.mul_f64(remaining),
+            });
+        };
+
+        self.settings
+            .tiles
+            .render_tracked(width, height, shade, on_tile, cancel)
+    }
+
+    /// Like `render`, handing every tile's pixels to `on_tile` from the worker threads as soon
+    /// as it finishes, so the image can be shown while it's still being rendered
+    pub fn render_tiles<P>(
+        &self,
+        on_tile: P,
+        cancel: &CancelToken,
+    ) -> Result<Framebuffer, Cancelled>
+    where
+        P: Fn(&Tile, &[Color]) + Sync,
+    {
+        let RenderSettings { width, height, .. } = self.settings;
+        let pixels = self.settings.tiles.render_tracked(
+            width,
+            height,
+            |x, y| self.render_pixel(x, y),
+            |tile, pixels, _, _| on_tile(tile, pixels),
+            cancel,
+        )?;
+
+        Ok(Framebuffer::from_pixels(width, height, pixels))
+    }
+}
+
+#[test]
+pub fn test_tiles_cover_frame() {
+    for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+        let tiles = tiles(70, 45, 16, order);
+        assert_eq!(tiles.len(), 5 * 3, "{order:?}");
+
+        let area: usize = tiles.iter().map(|t| t.width * t.height).sum();
+        assert_eq!(area, 70 * 45, "{order:?}");
+    }
+
+    let spiral = tiles(48, 48, 16, TileOrder::Spiral);
+    assert_eq!(
+        spiral[0],
+        Tile {
+            x: 16,
+            y: 16,
+            width: 16,
+            height: 16
+        }
+    );
+
+    // consecutive tiles along the Hilbert curve always share an edge
+    let hilbert = tiles(64, 64, 16, TileOrder::Hilbert);
+    for pair in hilbert.windows(2) {
+        let dx = pair[0].x.abs_diff(pair[1].x);
+        let dy = pair[0].y.abs_diff(pair[1].y);
+        assert_eq!(dx + dy, 16, "{pair:?}");
+    }
+}
+
+#[test]
+pub fn test_tile_render_stitch() {
+    let renderer = TileRenderer::new(7, TileOrder::Hilbert);
+    let frame = renderer.render(30, 20, |x, y| Color(x as f32, y as f32, 0.0));
+
+    for y in 0..20 {
+        for x in 0..30 {
+            assert_eq!(frame.get(x, y), Color(x as f32, y as f32, 0.0));
+        }
+    }
+}
+
+#[test]
+pub fn test_renderer_matches_trace_ray() {
+    let settings = RenderSettings {
+        width: 20,
+        height: 10,
+        ..Default::default()
+    };
+    let camera = Viewport::new(crate::Pos3::new(0.0, 0.0, -5.0), 1.0, 0.5);
+    let renderer = Renderer::new(Scene::default(), camera.clone(), settings);
+    let frame = renderer.render();
+
+    // the same loop `main` used to run by hand
+    for y in -5..5 {
+        for x in -10..10 {
+            let ray = camera.ray_from_coord(x, y, 20, 10);
+            let expected = renderer.scene.trace_ray(ray, 1.0, f32::MAX, 3);
+            let (rx, ry) = crate::topleft_rel(20, 10, x, y);
+            assert_eq!(frame.get(rx, ry), expected);
+        }
+    }
+
+    // the center of the sphere faces the camera
+    let (image, aovs) = renderer
+        .render_with_aovs(&[Aov::Depth], |_| {}, &CancelToken::new())
+        .unwrap();
+    assert_eq!(image, frame);
+    assert_eq!(aovs.get(Aov::Depth).unwrap().get(10, 4).0, 4.0);
+
+    // the variables come from the beauty samples without changing them
+    let mut renderer = renderer;
+    renderer.settings.samples = 4;
+    renderer.settings.integrator = Integrator::Path;
+    let (image, aovs) = renderer
+        .render_with_aovs(&[Aov::Depth, Aov::MaterialId], |_| {}, &CancelToken::new())
+        .unwrap();
+    assert_eq!(image, renderer.render());
+    assert_eq!(aovs.get(Aov::MaterialId).unwrap().get(10, 4).0, 1.0);
+    assert_eq!(aovs.get(Aov::MaterialId).unwrap().get(0, 0).0, 0.0);
+
+    // every variable comes from the pixel's first sample, even along the sphere's edge
+    for (x, y) in [(10, 4), (6, 4), (10, 0), (14, 5)] {
+        let mut rng = renderer.pixel_rng(x, y);
+        let ray = renderer.sample_ray(x, y, &mut rng);
+        let (_, primary) = renderer.trace(ray, &mut rng);
+        let first = renderer.scene.aov_sample(&primary);
+        assert_eq!(renderer.render_pixel_aov(x, y).1, first);
+        let ids = aovs.get(Aov::MaterialId).unwrap();
+        assert_eq!(ids.get(x, y), first.value(Aov::MaterialId));
+    }
+}
+
+#[test]
+pub fn test_render_progress_cancel() {
+    use std::sync::Mutex;
+
+    let settings = RenderSettings {
+        width: 16,
+        height: 16,
+        samples: 2,
+        tiles: TileRenderer::new(4, TileOrder::Scanline),
+        ..Default::default()
+    };
+    let mut renderer = Renderer::new(Scene::default(), Viewport::default(), settings);
+
+    let reports = Mutex::new(Vec::new());
+    let frame = renderer
+        .render_with_progress(|p| reports.lock().unwrap().push(*p), &CancelToken::new())
+        .unwrap();
+    assert_eq!(frame, renderer.render());
+
+    let reports = reports.into_inner().unwrap();
+    assert_eq!(reports.len(), 16);
+    let last = reports.iter().max_by_key(|p| p.tiles_done).unwrap();
+    assert_eq!(last.fraction(), 1.0);
+    assert_eq!(last.rays, 16 * 16 * 2);
+    assert_eq!(last.eta, std::time::Duration::ZERO);
+
+    // cancelling from the callback stops before the rest of the tiles are claimed
+    renderer.settings.tiles = TileRenderer::new(1, TileOrder::Scanline);
+    let cancel = CancelToken::new();
+    let result = renderer.render_with_progress(|_| cancel.cancel(), &cancel);
+    assert_eq!(result, Err(Cancelled));
+}
+
+#[test]
+pub fn test_motion_blur() {
+    use crate::{
+        animation::{Interpolation, Keyframe, Pose, Track, TransformTrack},
+        identity_matrix, Pos3, Ray, Vec3,
+    };
+    use std::sync::Arc;
+
+    // a unit sphere crossing the view, clear of the center by a quarter of the way across
+    let poses = [0.0, 4.0].map(|x| Keyframe {
+        time: x / 4.0,
+        value: Pose {
+            translate: Vec3::new(x, 0.0, 0.0),
+            ..Default::default()
+        },
+    });
+    let track = TransformTrack::new(
+        identity_matrix!(),
+        Track::new(poses.to_vec(), Interpolation::Linear),
+    )
+    .unwrap();
+    let mut scene = Scene::default();
+    scene.spheres = vec![Arc::new(
+        (*scene.spheres[0]).clone().with_motion(Arc::new(track)),
+    )];
+
+    let ray = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    // misses come back as NaN distances
+    let hits = |ray: Ray| ray.sphere_intersect(&scene.spheres[0])[0].t;
+    assert_eq!(hits(ray.clone()), 4.0);
+    assert!(hits(ray.with_time(1.0)).is_nan());
+
+    let settings = RenderSettings {
+        width: 1,
+        height: 1,
+        samples: 64,
+        ..Default::default()
+    };
+    let render = |open: f32, close: f32| {
+        let camera = Viewport::new(Pos3::new(0.0, 0.0, -5.0), 0.01, 0.01).with_shutter(open, close);
+        Renderer::new(scene.clone(), camera, settings).render_pixel(0, 0)
+    };
+
+    let (hit, miss) = (render(0.0, 0.0), render(1.0, 1.0));
+    assert_eq!(miss, scene.bg_color.into());
+    assert!(hit.0 > miss.0);
+
+    // open for the whole move, about a quarter of the samples still find the sphere
+    let blurred = (render(0.0, 1.0).0 - miss.0) / (hit.0 - miss.0);
+    assert!((0.1..0.4).contains(&blurred), "{blurred}");
+}
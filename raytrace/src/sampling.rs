@@ -0,0 +1,253 @@
+use std::f32::consts::PI;
+
+use crate::Vec3;
+
+/// Small, seedable PCG32 generator. Renders need to be reproducible, so every random decision
+/// goes through one of these rather than a thread-local source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+    inc: u64,
+}
+
+impl Rng {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    /// Generators with different `stream` values produce independent sequences even when they
+    /// share a seed
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.inc);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// The generator's full state, for saving and later restoring with `from_state`
+    pub fn state(&self) -> (u64, u64) {
+        (self.state, self.inc)
+    }
+
+    pub fn from_state(state: u64, inc: u64) -> Self {
+        Self { state, inc }
+    }
+
+    /// Returns a value in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+}
+
+/// Returns a direction in the local +Z hemisphere, distributed proportional to its cosine with
+/// the pole. The pdf is `z / PI`.
+pub fn cosine_hemisphere(rng: &mut Rng) -> Vec3 {
+    let r = rng.next_f32().sqrt();
+    let phi = 2.0 * PI * rng.next_f32();
+    let (sin, cos) = phi.sin_cos();
+
+    Vec3::new(r * cos, r * sin, (1.0 - (r * r)).max(0.0).sqrt())
+}
+
+/// Returns a direction anywhere on the unit sphere. The pdf is `1 / (4 * PI)`.
+pub fn uniform_sphere(rng: &mut Rng) -> Vec3 {
+    let z = 1.0 - (2.0 * rng.next_f32());
+    let r = (1.0 - (z * z)).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.next_f32();
+    let (sin, cos) = phi.sin_cos();
+
+    Vec3::new(r * cos, r * sin, z)
+}
+
+/// Multiple importance sampling weight for a sample drawn from the strategy with `pdf_a`
+pub fn power_heuristic(pdf_a: f32, pdf_b: f32) -> f32 {
+    let a = pdf_a * pdf_a;
+    let b = pdf_b * pdf_b;
+
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+/// Piecewise-constant distribution over [0, 1), used to draw samples proportional to a tabulated
+/// function
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f32>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + (func[i - 1].abs() / n as f32);
+        }
+
+        let integral = cdf[n];
+
+        if n == 0 {
+            // an empty function, which `sample` gives a zero pdf
+        } else if integral == 0.0 {
+            // nothing to prefer, so fall back to uniform
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f32 / n as f32;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /// Returns the sampled position in [0, 1), its pdf, and the index of the bucket it fell in.
+    /// An empty function has nothing to sample, so that's position 0 with a pdf of 0.
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        if self.is_empty() {
+            return (0.0, 0.0, 0);
+        }
+
+        // last bucket whose cdf is <= u
+        let index = self
+            .cdf
+            .partition_point(|c| *c <= u)
+            .saturating_sub(1)
+            .min(self.len() - 1);
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+
+        (
+            (index as f32 + offset) / self.len() as f32,
+            self.pdf(index),
+            index,
+        )
+    }
+
+    pub fn pdf(&self, index: usize) -> f32 {
+        if index >= self.len() {
+            0.0
+        } else if self.integral == 0.0 {
+            1.0
+        } else {
+            self.func[index].abs() / self.integral
+        }
+    }
+}
+
+/// Piecewise-constant distribution over [0, 1)^2, sampled row first and then column
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `func` is in row-major order
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        let rows: Vec<Distribution1D> = (0..height)
+            .map(|y| {
+                Distribution1D::new(func.iter().skip(y * width).take(width).copied().collect())
+            })
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|r| r.integral()).collect());
+
+        Self { rows, marginal }
+    }
+
+    /// Returns the sampled (x, y) position and its pdf with respect to area in [0, 1)^2
+    pub fn sample(&self, u1: f32, u2: f32) -> ((f32, f32), f32) {
+        let (y, pdf_y, row) = self.marginal.sample(u2);
+        let Some(row) = self.rows.get(row) else {
+            return ((0.0, y), 0.0);
+        };
+        let (x, pdf_x, _) = row.sample(u1);
+
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        let height = self.marginal.len();
+        let Some(width) = self.rows.first().map(|r| r.len()) else {
+            return 0.0;
+        };
+        let row = ((y * height as f32) as usize).min(height - 1);
+        let col = ((x * width as f32) as usize).min(width.saturating_sub(1));
+
+        self.marginal.pdf(row) * self.rows[row].pdf(col)
+    }
+}
+
+#[test]
+pub fn test_rng_reproducible() {
+    let mut a = Rng::new(42, 7);
+    let mut b = Rng::new(42, 7);
+    let mut c = Rng::new(42, 8);
+
+    let seq_a: Vec<u32> = (0..16).map(|_| a.next_u32()).collect();
+    let seq_b: Vec<u32> = (0..16).map(|_| b.next_u32()).collect();
+    let seq_c: Vec<u32> = (0..16).map(|_| c.next_u32()).collect();
+
+    assert_eq!(seq_a, seq_b);
+    assert_ne!(seq_a, seq_c);
+    assert!((0..1000).all(|_| (0.0..1.0).contains(&a.next_f32())));
+}
+
+#[test]
+pub fn test_distribution_1d() {
+    let dist = Distribution1D::new(vec![0.0, 3.0, 1.0, 0.0]);
+
+    // the first and last buckets can never be picked
+    let (x, pdf, index) = dist.sample(0.0);
+    assert_eq!(index, 1);
+    assert!(crate::float_eq(x, 0.25));
+    assert!(crate::float_eq(pdf, 3.0));
+
+    let (x, _, index) = dist.sample(0.875);
+    assert_eq!(index, 2);
+    assert!(crate::float_eq(x, 0.625));
+
+    // nothing to sample, like a row of a 0 pixel wide environment map
+    assert_eq!(Distribution1D::new(Vec::new()).sample(0.5), (0.0, 0.0, 0));
+    let empty = Distribution2D::new(&[], 0, 4);
+    assert_eq!(empty.sample(0.5, 0.5).1, 0.0);
+    assert_eq!(empty.pdf(0.5, 0.5), 0.0);
+}
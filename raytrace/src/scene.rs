@@ -1,17 +1,29 @@
 use std::sync::Arc;
 
 use crate::{
+    aov::PrimaryHit,
+    environment::Environment,
     identity_matrix,
+    media::Fog,
+    occlusion::AoSettings,
     objects::{material::Material, Sphere},
-    primitives::color,
-    Color, Matrix, Object, PointLight, Pos3, Ray, Surface, Vec3,
+    Color, DirectionalLight, Mat4, Object, PointLight, Pos3, Ray, Vec3,
 };
 
+/// Minimum distance for secondary rays, so they don't hit the surface they start on
+pub const SURFACE_EPSILON: f32 = 0.001;
+
 #[derive(Debug)]
 pub struct Scene {
     pub spheres: Vec<Arc<Sphere>>,
     pub lights: Vec<PointLight>,
     pub bg_color: [u8; 3],
+    /// Seen by rays that miss every object. Takes priority over `bg_color` when present.
+    pub environment: Option<Environment>,
+    /// Scales the `ambient` term of `compute_lighting` by how occluded each point is
+    pub ao: Option<AoSettings>,
+    /// A medium filling the whole scene
+    pub fog: Option<Fog>,
 }
 
 impl Clone for Scene {
@@ -20,6 +32,9 @@ impl Clone for Scene {
             spheres: self.spheres.clone(),
             lights: self.lights.clone(),
             bg_color: self.bg_color,
+            environment: self.environment.clone(),
+            ao: self.ao,
+            fog: self.fog.clone(),
         }
     }
 }
@@ -35,36 +50,63 @@ impl Default for Scene {
                     diffuse: 0.7,
                     specular: 0.2,
                     shine: 0.0,
+                    texture: None,
+                    normal_map: None,
+                    id: Some(0),
                 },
             )
             .into(),
             Sphere::new(
-                Matrix::scaling(0.5, 0.5, 0.5),
-                Material::default(),
+                Mat4::scaling(0.5, 0.5, 0.5),
+                Material::default().with_id(1),
             ).into()],
             lights: vec![PointLight::new(
                 Pos3::new(-10.0, 10.0, -10.0),
                 Color(1.0, 1.0, 1.0),
             )],
             bg_color: Default::default(),
+            environment: None,
+            ao: None,
+            fog: None,
         }
     }
 }
 impl Scene {
-    pub fn trace_ray(&self, ray: Ray, t_min: f32, t_max: f32, depth: usize) -> [u8; 3] {
+    /// Returns unclamped linear color, see `Framebuffer` for turning it into an image
+    pub fn trace_ray(&self, ray: Ray, t_min: f32, t_max: f32, depth: usize) -> Color {
+        self.trace_ray_hit(ray, t_min, t_max, depth).0
+    }
+
+    /// Like `trace_ray`, also returning the closest hit
+    pub(crate) fn trace_ray_hit(
+        &self,
+        ray: Ray,
+        t_min: f32,
+        t_max: f32,
+        _depth: usize,
+    ) -> (Color, PrimaryHit) {
         let intersects = self.get_intersections(&ray, t_min, t_max);
         let closest = self.get_closest(intersects);
 
-        match closest {
-            None => self.bg_color,
+        // media only attenuate here, in-scattering needs the path tracer
+        let t_hit = closest.as_ref().map_or(t_max, |hit| hit.t);
+        let attenuation = self.media_transmittance(&ray, t_min, t_hit);
+
+        let color = match &closest {
+            None => self.background(ray.dir) * attenuation,
             Some(hit) => {
-                if let Object::Sphere(obj) = hit.obj {
-                    let point = ray.position(hit.t);
-                    self.compute_lighting(point, obj.normal_at(point), ray.dir, &obj.material)
-                        .into()
-                } else {
-                    todo!()
-                }
+                let Object::Sphere(obj) = &hit.obj;
+                let obj = obj.at_time(ray.time);
+                let point = ray.position(hit.t);
+                let color = self.compute_lighting_at(
+                    point,
+                    obj.shading_normal_at(point),
+                    ray.dir,
+                    &obj.material_at(point),
+                    ray.time,
+                );
+
+                color * attenuation
                 // let p = ray.origin + hit.t * ray.dir;
                 // let n = (p - sph.center).to_normalized();
 
@@ -82,17 +124,44 @@ impl Scene {
 
                 // ((local_color * (1.0 - reflect)) + (reflect_color * reflect)).into()
             }
+        };
+
+        (color, PrimaryHit { ray, hit: closest })
+    }
+
+    /// Returns the light seen by a ray travelling in direction `dir` that hits nothing
+    pub fn background(&self, dir: Vec3) -> Color {
+        match &self.environment {
+            Some(env) => env.radiance(dir),
+            None => self.bg_color.into(),
         }
     }
 
+    /// The environment's sun, if it has one
+    pub fn sun(&self) -> Option<DirectionalLight> {
+        self.environment.as_ref().and_then(|env| env.sun())
+    }
+
+    /// Returns true if anything lies along the ray between `SURFACE_EPSILON` and `t_max`
+    pub fn is_occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        !self
+            .get_intersections(ray, SURFACE_EPSILON, t_max)
+            .is_empty()
+    }
+
     pub fn get_intersections(&self, ray: &Ray, t_min: f32, t_max: f32) -> Vec<Intersection> {
         let mut intersects: Vec<Vec<Intersection>> = Vec::new();
 
-        for sphere in self.spheres.iter() {
+        // spheres holding a medium aren't solid, see `Scene::media_segments`
+        for sphere in self.spheres.iter().filter(|s| s.medium.is_none()) {
             intersects.push(ray.sphere_intersect(sphere));
         }
 
-        intersects.into_iter().flatten().collect()
+        intersects
+            .into_iter()
+            .flatten()
+            .filter(|x| x.t >= t_min && x.t <= t_max)
+            .collect()
     }
 
     pub fn get_closest(&self, intersects: Vec<Intersection>) -> Option<Intersection> {
@@ -108,14 +177,32 @@ impl Scene {
         normal_vec: Vec3,
         cam_vec: Vec3,
         material: &Material,
+    ) -> Color {
+        self.compute_lighting_at(point, normal_vec, cam_vec, material, 0.0)
+    }
+
+    /// Like `compute_lighting`, for a point seen at `time`
+    pub fn compute_lighting_at(
+        &self,
+        point: Pos3,
+        normal_vec: Vec3,
+        cam_vec: Vec3,
+        material: &Material,
+        time: f32,
     ) -> Color {
         let mut result = Color::BLACK;
+        let ambient_factor = self.ambient_factor(point, normal_vec, time);
+
+        let point_lights = self
+            .lights
+            .iter()
+            .map(|light| ((light.position - point).to_normalized(), light.intensity));
+        let sun = self.sun().map(|sun| (sun.direction, sun.intensity));
 
-        for light in &self.lights {
-            let effective_color = material.color * light.intensity;
-            let light_vec = (light.position - point).to_normalized();
+        for (light_vec, intensity) in point_lights.chain(sun) {
+            let effective_color = material.color * intensity;
 
-            let ambient = effective_color * material.ambient;
+            let ambient = effective_color * (material.ambient * ambient_factor);
 
             let l_dot_n = light_vec * normal_vec;
 
@@ -129,7 +216,7 @@ impl Scene {
                     Color::BLACK
                 } else {
                     let factor = r_dot_c.powf(material.shine);
-                    light.intensity * material.specular * factor
+                    intensity * material.specular * factor
                 };
 
                 (effective_color * material.diffuse * l_dot_n, specular)
@@ -190,6 +277,7 @@ pub fn test_lighting_behindcam() {
             Color(1.0, 1.0, 1.0),
         )],
         bg_color: [0, 0, 0],
+        ..Default::default()
     };
 
     let result = scene.compute_lighting(
@@ -216,6 +304,7 @@ pub fn test_lighting_eyeoffset() {
             Color(1.0, 1.0, 1.0),
         )],
         bg_color: [0, 0, 0],
+        ..Default::default()
     };
 
     let result = scene.compute_lighting(
@@ -242,6 +331,7 @@ pub fn test_lighting_lightoffset() {
             Color(1.0, 1.0, 1.0),
         )],
         bg_color: [0, 0, 0],
+        ..Default::default()
     };
 
     let result = scene.compute_lighting(
@@ -268,6 +358,7 @@ pub fn test_lighting_bothoffset() {
             Color(1.0, 1.0, 1.0),
         )],
         bg_color: [0, 0, 0],
+        ..Default::default()
     };
 
     let result = scene.compute_lighting(
@@ -294,6 +385,7 @@ pub fn test_lighting_behindobj() {
             Color(1.0, 1.0, 1.0),
         )],
         bg_color: [0, 0, 0],
+        ..Default::default()
     };
 
     let result = scene.compute_lighting(
@@ -305,3 +397,20 @@ pub fn test_lighting_behindobj() {
 
     assert_eq!(result, Color(0.1, 0.1, 0.1));
 }
+
+#[test]
+pub fn test_intersections_in_range() {
+    let scene = Scene {
+        spheres: vec![Arc::new(Sphere::new(identity_matrix!(), Material::default()))],
+        ..Default::default()
+    };
+    let ray = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    let ts = |t_min, t_max| -> Vec<f32> {
+        scene.get_intersections(&ray, t_min, t_max).iter().map(|x| x.t).collect()
+    };
+
+    assert_eq!(ts(0.0, f32::MAX), [4.0, 6.0]);
+    assert_eq!(ts(0.0, 5.0), [4.0]);
+    assert_eq!(ts(5.0, f32::MAX), [6.0]);
+    assert!(ts(7.0, f32::MAX).is_empty());
+}
@@ -0,0 +1,1364 @@
+//! Loads scenes from the YAML format used by *The Ray Tracer Challenge*. A file is a list of
+//! entries, each either adding something to the scene or defining a named value for reuse:
+//!
+//! ```yaml
+//! - add: camera
+//!   width: 400
+//!   height: 200
+//!   field-of-view: 1.047
+//!   from: [0, 1.5, -5]
+//!   to: [0, 1, 0]
+//!   up: [0, 1, 0]
+//!
+//! - add: light
+//!   at: [-10, 10, -10]
+//!   intensity: [1, 1, 1]
+//!
+//! - add: background
+//!   color: [20, 20, 30]   # 8-bit sRGB, like `Scene::bg_color`
+//!
+//! - define: shiny
+//!   value:
+//!     color: [1, 0.2, 1]
+//!     specular: 0.9
+//!     shininess: 200
+//!
+//! - define: matte-shiny
+//!   extend: shiny
+//!   value:
+//!     specular: 0.1
+//!
+//! - define: lift
+//!   value:
+//!     - [translate, 0, 1, 0]
+//!
+//! - add: sphere
+//!   material: shiny
+//!   transform:
+//!     - [scale, 0.5, 0.5, 0.5]
+//!     - lift
+//!     - [rotate-x, 0.5]
+//!     - [shear, 1, 0, 0, 0, 0, 0]
+//! ```
+//!
+//! Transforms are applied in the order they're listed. Besides the ones above there's
+//! `[matrix, ...]`, taking all 16 numbers of a `Mat4` row by row. Materials take `color`,
+//! `ambient`, `diffuse`, `specular` and `shininess`, and start from `Material::default()`.
+//! Spheres naming the same defined material share its `Material::id`, any other material gets an
+//! ID of its own.
+//!
+//! Spheres, lights and the camera can also take `keyframes`, a list of `keys` each with a `time`
+//! in seconds and any of the values that change, plus an optional `interpolation` of `linear`
+//! (the default) or `cubic`. Values a key leaves out keep their unanimated value:
+//!
+//! ```yaml
+//! - add: sphere
+//!   transform:
+//!     - [translate, 1, 0, 0]
+//!   keyframes:
+//!     interpolation: cubic
+//!     keys:
+//!       - time: 0
+//!       - time: 1
+//!         rotate: [0, 2.094, 0]    # radians around X, Y and Z, or a quaternion [w, x, y, z]
+//!         translate: [0, 0.5, 0]
+//!         scale: [1, 1, 1]
+//!
+//! - add: light
+//!   at: [-10, 10, -10]
+//!   intensity: [1, 1, 1]
+//!   keyframes:
+//!     keys:
+//!       - time: 0
+//!       - time: 2
+//!         at: [10, 10, -10]
+//!         intensity: [0.5, 0.5, 0.5]
+//! ```
+//!
+//! A sphere's keyframes are applied after its `transform`, and the camera's take `from` and `to`.
+//! Rotations turn the short way between keys, so keep keys less than half a turn apart. Loaded
+//! scenes are posed at time 0, see `SceneFile::at` for the rest.
+//!
+//! Moving spheres blur when the camera has a `shutter: [open, close]`, in seconds from the time
+//! each frame is posed at. Left out, the shutter opens and closes at once and nothing blurs.
+//!
+//! A sphere with a `medium` is an invisible boundary around fog, smoke or murky liquid instead of
+//! a solid surface, see `Medium`. `noise` makes the density vary, with a scale and a number of
+//! octaves:
+//!
+//! ```yaml
+//! - add: sphere
+//!   medium:
+//!     absorption: [0.1, 0.1, 0.1]
+//!     scattering: [0.5, 0.5, 0.5]
+//!     anisotropy: 0.3
+//!     noise: [2, 4]
+//! ```
+//!
+//! Instead of `field-of-view`, a camera can give the size of its `view` directly, as the width
+//! and height of the window one unit in front of it. Instead of `to` and `up`, it can give its
+//! `orientation` as the 16 numbers of a `Mat4`, turning camera-space directions into world space.
+//! Neither depends on the image's aspect ratio or on rounding, which is how `SceneFile::to_yaml`
+//! writes cameras back out. Scenes with anything else the format can't describe, like textures or
+//! an environment, can't be saved at all rather than coming back different.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    fs, io,
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    animation::{
+        Animation, CameraKey, CameraTrack, Interpolate, Interpolation, Keyframe, LightKey, Pose,
+        Track, TransformTrack,
+    },
+    identity_matrix,
+    media::{Density, Medium},
+    objects::{material::Material, Sphere},
+    render::RenderSettings,
+    yaml::{self, Node, ParseError, Value},
+    Color, Mat4, PointLight, Pos3, Quat, Scene, Vec3, Viewport,
+};
+
+/// Everything a scene file describes
+#[derive(Debug, Clone)]
+pub struct SceneFile {
+    pub scene: Scene,
+    pub camera: Viewport,
+    /// Image size in pixels
+    pub width: usize,
+    pub height: usize,
+    /// Keyframes, with the unanimated values they start from
+    pub animation: Animation,
+    /// When the camera's shutter opens and closes, in seconds from the time passed to `at`
+    pub shutter: (f32, f32),
+}
+
+impl Default for SceneFile {
+    fn default() -> Self {
+        let settings = RenderSettings::default();
+        Self {
+            scene: Scene {
+                spheres: Vec::new(),
+                lights: Vec::new(),
+                ..Default::default()
+            },
+            camera: Viewport::default(),
+            width: settings.width,
+            height: settings.height,
+            animation: Animation::default(),
+            shutter: (0.0, 0.0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// Saving a scene with something a scene file can't describe
+    Unsupported(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{e}"),
+            SceneError::Parse(e) => write!(f, "{e}"),
+            SceneError::Unsupported(what) => write!(f, "scene files can't describe {what}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(value: io::Error) -> Self {
+        SceneError::Io(value)
+    }
+}
+
+impl From<ParseError> for SceneError {
+    fn from(value: ParseError) -> Self {
+        SceneError::Parse(value)
+    }
+}
+
+impl SceneFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let source = fs::read_to_string(path)?;
+        Ok(Self::parse(&source)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let root = yaml::parse(source)?;
+        let Node::List(entries) = root.node else {
+            return Err(ParseError::new(
+                root.pos,
+                "a scene file should be a list of `add` and `define` entries",
+            ));
+        };
+
+        let mut loader = Loader {
+            file: SceneFile::default(),
+            defines: HashMap::new(),
+            material_ids: HashMap::new(),
+            materials: 0,
+        };
+        for entry in &entries {
+            loader.entry(entry)?;
+        }
+
+        Ok(loader.file.at(0.0))
+    }
+
+    /// A copy with everything animated moved to where it is at `time`, in seconds, and the
+    /// shutter open from then on
+    pub fn at(&self, time: f32) -> SceneFile {
+        let mut file = self.clone();
+        let (open, close) = self.shutter;
+        file.camera.shutter = (time + open, time + close);
+        self.animation
+            .apply(&mut file.scene, &mut file.camera, time);
+        file
+    }
+
+    /// Writes the file back out as text that `parse` turns into the same scene. Every sphere gets
+    /// a single `matrix` transform, so nothing is lost to rounding, and materials shared between
+    /// spheres are written as a `define` so they keep sharing a `Material::id`. Fails
+    /// with `SceneError::Unsupported` if the scene holds anything the format can't describe.
+    pub fn to_yaml(&self) -> Result<String, SceneError> {
+        self.check_supported()?;
+
+        let mut out = String::new();
+        self.write_yaml(&mut out)
+            .expect("writing to a String can't fail");
+        Ok(out)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        Ok(fs::write(path, self.to_yaml()?)?)
+    }
+
+    /// Everything `write_yaml` would otherwise have to leave out
+    fn check_supported(&self) -> Result<(), SceneError> {
+        let unsupported = |what: String| Err(SceneError::Unsupported(what));
+        let scene = &self.scene;
+        if scene.environment.is_some() {
+            return unsupported("an environment".to_string());
+        }
+        if scene.fog.is_some() {
+            return unsupported("fog".to_string());
+        }
+        if scene.ao.is_some() {
+            return unsupported("ambient occlusion settings".to_string());
+        }
+
+        for (index, sphere) in scene.spheres.iter().enumerate() {
+            if sphere.material.texture.is_some() {
+                return unsupported(format!("the texture on sphere {index}"));
+            }
+            if sphere.material.normal_map.is_some() {
+                return unsupported(format!("the normal map on sphere {index}"));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_yaml(&self, out: &mut String) -> fmt::Result {
+        let camera = &self.camera;
+
+        writeln!(out, "- add: camera")?;
+        writeln!(out, "  width: {}", self.width)?;
+        writeln!(out, "  height: {}", self.height)?;
+        writeln!(out, "  view: [{}, {}]", camera.width, camera.height)?;
+        writeln!(out, "  from: {}", list(camera.position.iter()))?;
+        match &self.animation.camera {
+            // the keys turn the camera with `look_at`, so they need the `up` they were given
+            Some(track) => {
+                let to = camera.position + camera.forward();
+                writeln!(out, "  to: {}", list(to.iter()))?;
+                writeln!(out, "  up: {}", list(track.up.iter()))?;
+            }
+            None => {
+                let values = camera.orientation.0.iter().flatten().copied();
+                writeln!(out, "  orientation: [{}]", join(values))?;
+            }
+        }
+        if self.shutter != (0.0, 0.0) {
+            writeln!(out, "  shutter: [{}, {}]", self.shutter.0, self.shutter.1)?;
+        }
+        if let Some(track) = &self.animation.camera {
+            write_keyframes(out, &track.keys, |key| {
+                vec![("from", list(key.from.iter())), ("to", list(key.to.iter()))]
+            })?;
+        }
+
+        let [r, g, b] = self.scene.bg_color;
+        writeln!(out, "\n- add: background")?;
+        writeln!(out, "  color: [{r}, {g}, {b}]")?;
+
+        for (index, light) in self.scene.lights.iter().enumerate() {
+            writeln!(out, "\n- add: light")?;
+            writeln!(out, "  at: {}", list(light.position.iter()))?;
+            writeln!(out, "  intensity: {}", rgb(light.intensity))?;
+
+            if let Some((_, track)) = self.animation.lights.iter().find(|(i, _)| *i == index) {
+                write_keyframes(out, track, |key| {
+                    vec![
+                        ("at", list(key.at.iter())),
+                        ("intensity", rgb(key.intensity)),
+                    ]
+                })?;
+            }
+        }
+
+        // materials shared by several spheres are defined once, so they keep sharing an ID
+        let mut shared: Vec<usize> = Vec::new();
+        for (index, sphere) in self.scene.spheres.iter().enumerate() {
+            let Some(id) = sphere.material.id else {
+                continue;
+            };
+            let later = &self.scene.spheres[index + 1..];
+            if !shared.contains(&id) && later.iter().any(|s| s.material.id == Some(id)) {
+                writeln!(out, "\n- define: material-{id}")?;
+                writeln!(out, "  value:")?;
+                write_material(out, &sphere.material)?;
+                shared.push(id);
+            }
+        }
+
+        for (index, sphere) in self.scene.spheres.iter().enumerate() {
+            let track = self
+                .animation
+                .spheres
+                .iter()
+                .find(|(i, _)| *i == index)
+                .map(|(_, track)| track);
+
+            writeln!(out, "\n- add: sphere")?;
+            match sphere.material.id.filter(|id| shared.contains(id)) {
+                Some(id) => writeln!(out, "  material: material-{id}")?,
+                None => {
+                    writeln!(out, "  material:")?;
+                    write_material(out, &sphere.material)?;
+                }
+            }
+
+            if let Some(medium) = &sphere.medium {
+                writeln!(out, "  medium:")?;
+                writeln!(out, "    absorption: {}", rgb(medium.absorption))?;
+                writeln!(out, "    scattering: {}", rgb(medium.scattering))?;
+                writeln!(out, "    anisotropy: {}", medium.anisotropy)?;
+                if let Density::Noise { scale, octaves } = medium.density {
+                    writeln!(out, "    noise: [{scale}, {octaves}]")?;
+                }
+            }
+
+            // animated spheres are written unposed, since the keys are applied on top
+            let transform = track.map_or(sphere.transform(), |track| track.base());
+            let values = transform.0.iter().flatten().copied();
+            writeln!(out, "  transform:")?;
+            writeln!(out, "    - [matrix, {}]", join(values))?;
+
+            if let Some(track) = track {
+                write_keyframes(out, track.poses(), |pose| {
+                    let q = pose.rotate;
+                    vec![
+                        ("translate", list(pose.translate.iter())),
+                        ("rotate", list([q.w, q.x, q.y, q.z].into_iter())),
+                        ("scale", list(pose.scale.iter())),
+                    ]
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The default render settings, at this file's resolution
+    pub fn render_settings(&self) -> RenderSettings {
+        RenderSettings {
+            width: self.width,
+            height: self.height,
+            ..Default::default()
+        }
+    }
+}
+
+/// `f32`'s `Display` picks the shortest text that parses back to the same number
+fn join(values: impl Iterator<Item = f32>) -> String {
+    values.map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn list(values: impl Iterator<Item = f32>) -> String {
+    format!("[{}]", join(values))
+}
+
+fn rgb(color: Color) -> String {
+    list([color.0, color.1, color.2].into_iter())
+}
+
+fn write_material(out: &mut String, m: &Material) -> fmt::Result {
+    writeln!(out, "    color: {}", rgb(m.color))?;
+    writeln!(out, "    ambient: {}", m.ambient)?;
+    writeln!(out, "    diffuse: {}", m.diffuse)?;
+    writeln!(out, "    specular: {}", m.specular)?;
+    writeln!(out, "    shininess: {}", m.shine)
+}
+
+/// Writes every key in full, so none of them depend on the unanimated values
+fn write_keyframes<T: Interpolate>(
+    out: &mut String,
+    track: &Track<T>,
+    fields: impl Fn(&T) -> Vec<(&'static str, String)>,
+) -> fmt::Result {
+    writeln!(out, "  keyframes:")?;
+    writeln!(out, "    interpolation: {}", track.interpolation.name())?;
+    writeln!(out, "    keys:")?;
+    for key in track.keys() {
+        writeln!(out, "      - time: {}", key.time)?;
+        for (name, value) in fields(&key.value) {
+            writeln!(out, "        {name}: {value}")?;
+        }
+    }
+    Ok(())
+}
+
+fn error<T>(value: &Value, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError::new(value.pos, message))
+}
+
+fn number(value: &Value) -> Result<f32, ParseError> {
+    match value.node {
+        Node::Number(n) => Ok(n),
+        _ => error(value, format!("expected a number, found {}", value.kind())),
+    }
+}
+
+fn string(value: &Value) -> Result<&str, ParseError> {
+    match &value.node {
+        Node::String(s) => Ok(s),
+        _ => error(value, format!("expected a name, found {}", value.kind())),
+    }
+}
+
+fn size(value: &Value) -> Result<usize, ParseError> {
+    let n = number(value)?;
+    if n < 1.0 || n.fract() != 0.0 {
+        return error(value, "expected a positive whole number");
+    }
+    Ok(n as usize)
+}
+
+fn triple(value: &Value) -> Result<[f32; 3], ParseError> {
+    match &value.node {
+        Node::List(items) if items.len() == 3 => {
+            Ok([number(&items[0])?, number(&items[1])?, number(&items[2])?])
+        }
+        _ => error(value, "expected a list of 3 numbers"),
+    }
+}
+
+fn point(value: &Value) -> Result<Pos3, ParseError> {
+    let [x, y, z] = triple(value)?;
+    Ok(Pos3::new(x, y, z))
+}
+
+fn vector(value: &Value) -> Result<Vec3, ParseError> {
+    let [x, y, z] = triple(value)?;
+    Ok(Vec3::new(x, y, z))
+}
+
+fn color(value: &Value) -> Result<Color, ParseError> {
+    let [r, g, b] = triple(value)?;
+    Ok(Color(r, g, b))
+}
+
+fn entries(value: &Value) -> Result<&[(String, Value)], ParseError> {
+    match &value.node {
+        Node::Map(entries) => Ok(entries),
+        _ => error(value, format!("expected a mapping, found {}", value.kind())),
+    }
+}
+
+/// Stops at the first key that isn't in `allowed`
+fn check_keys(entries: &[(String, Value)], allowed: &[&str], what: &str) -> Result<(), ParseError> {
+    for (key, value) in entries {
+        if !allowed.contains(&key.as_str()) {
+            return error(
+                value,
+                format!(
+                    "unknown key `{key}` for {what}, expected one of: {}",
+                    allowed.join(", ")
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn get<'a>(entries: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+    entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn require<'a>(
+    entries: &'a [(String, Value)],
+    key: &str,
+    parent: &Value,
+    what: &str,
+) -> Result<&'a Value, ParseError> {
+    get(entries, key).map_or_else(|| error(parent, format!("{what} is missing `{key}`")), Ok)
+}
+
+/// Reads `keyframes`, where each key may hold `time` and any of `allowed`. `key` turns the
+/// fields of one key into its value.
+fn keyframes<T: Interpolate>(
+    value: &Value,
+    allowed: &[&str],
+    what: &str,
+    mut key: impl FnMut(&Value, &[(String, Value)]) -> Result<T, ParseError>,
+) -> Result<Track<T>, ParseError> {
+    let fields = entries(value)?;
+    check_keys(fields, &["interpolation", "keys"], "keyframes")?;
+
+    let interpolation = match get(fields, "interpolation") {
+        Some(v) => {
+            let name = string(v)?;
+            Interpolation::from_name(name).map_or_else(
+                || {
+                    error(
+                        v,
+                        format!("unknown interpolation `{name}`, expected linear or cubic"),
+                    )
+                },
+                Ok,
+            )?
+        }
+        None => Interpolation::default(),
+    };
+
+    let list = require(fields, "keys", value, "keyframes")?;
+    let items = match &list.node {
+        Node::List(items) if !items.is_empty() => items,
+        _ => return error(list, "expected a list of keys"),
+    };
+
+    let allowed: Vec<&str> = ["time"].iter().chain(allowed).copied().collect();
+    let mut keys = Vec::new();
+    for item in items {
+        let key_fields = entries(item)?;
+        check_keys(key_fields, &allowed, what)?;
+        let time = number(require(key_fields, "time", item, what)?)?;
+        keys.push(Keyframe {
+            time,
+            value: key(item, key_fields)?,
+        });
+    }
+
+    Ok(Track::new(keys, interpolation))
+}
+
+/// Either 3 angles for `Quat::from_euler` or the 4 parts of a quaternion
+fn rotation(value: &Value) -> Result<Quat, ParseError> {
+    let parts = match &value.node {
+        Node::List(items) if items.len() == 3 || items.len() == 4 => {
+            items.iter().map(number).collect::<Result<Vec<_>, _>>()?
+        }
+        _ => return error(value, "expected 3 angles or the 4 numbers of a quaternion"),
+    };
+
+    match parts[..] {
+        [x, y, z] => Ok(Quat::from_euler(x, y, z)),
+        [w, x, y, z] => {
+            let q = Quat::new(w, x, y, z);
+            let length = q.dot(q);
+            if length.is_nan() || length <= 1e-12 {
+                return error(value, "a quaternion can't be all zeros");
+            }
+            Ok(q.normalized())
+        }
+        _ => unreachable!("lengths were checked above"),
+    }
+}
+
+fn pose(fields: &[(String, Value)]) -> Result<Pose, ParseError> {
+    let mut pose = Pose::default();
+    for (key, v) in fields {
+        match key.as_str() {
+            "translate" => pose.translate = vector(v)?,
+            "rotate" => pose.rotate = rotation(v)?,
+            "scale" => {
+                pose.scale = vector(v)?;
+                if pose.scale.iter().any(|c| c == 0.0) {
+                    return error(v, "scale can't be zero, the sphere would vanish");
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(pose)
+}
+
+fn medium(value: &Value) -> Result<Medium, ParseError> {
+    let fields = entries(value)?;
+    check_keys(
+        fields,
+        &["absorption", "scattering", "anisotropy", "noise"],
+        "a medium",
+    )?;
+
+    let mut medium = Medium::homogeneous(Color::BLACK, Color::BLACK);
+    for (key, v) in fields {
+        match key.as_str() {
+            "absorption" => medium.absorption = color(v)?,
+            "scattering" => medium.scattering = color(v)?,
+            "anisotropy" => {
+                medium.anisotropy = number(v)?;
+                if !(-1.0..=1.0).contains(&medium.anisotropy) {
+                    return error(v, "anisotropy should be between -1 and 1");
+                }
+            }
+            "noise" => {
+                let (scale, octaves) = match &v.node {
+                    Node::List(items) if items.len() == 2 => (number(&items[0])?, &items[1]),
+                    _ => return error(v, "expected a scale and a number of octaves"),
+                };
+                medium.density = Density::Noise {
+                    scale,
+                    octaves: size(octaves)? as u32,
+                };
+            }
+            _ => unreachable!("keys were checked above"),
+        }
+    }
+    Ok(medium)
+}
+
+struct Loader {
+    file: SceneFile,
+    /// Values are stored with `extend` and any names inside transform lists already resolved
+    defines: HashMap<String, Value>,
+    /// IDs of the defined materials used so far
+    material_ids: HashMap<String, usize>,
+    /// Material IDs handed out so far
+    materials: usize,
+}
+
+impl Loader {
+    fn entry(&mut self, entry: &Value) -> Result<(), ParseError> {
+        let fields = entries(entry)?;
+
+        if let Some(kind) = get(fields, "add") {
+            match string(kind)? {
+                "camera" => self.camera(entry, fields),
+                "light" => self.light(entry, fields),
+                "background" => self.background(entry, fields),
+                "sphere" => self.sphere(fields),
+                other => error(
+                    kind,
+                    format!("can't add `{other}`, expected camera, light, background or sphere"),
+                ),
+            }
+        } else if let Some(name) = get(fields, "define") {
+            self.define(entry, name, fields)
+        } else {
+            error(entry, "expected an `add` or `define` entry")
+        }
+    }
+
+    fn define(
+        &mut self,
+        entry: &Value,
+        name: &Value,
+        fields: &[(String, Value)],
+    ) -> Result<(), ParseError> {
+        check_keys(fields, &["define", "extend", "value"], "define")?;
+        let name = string(name)?.to_string();
+        let value = require(fields, "value", entry, "define")?;
+
+        let value = match (&value.node, get(fields, "extend")) {
+            (Node::Map(overrides), Some(base_name)) => {
+                let base = self.lookup(base_name)?;
+                let Node::Map(base_entries) = &base.node else {
+                    return error(base_name, "only mappings can be extended");
+                };
+
+                let mut merged = base_entries.clone();
+                for (key, v) in overrides {
+                    match merged.iter_mut().find(|(k, _)| k == key) {
+                        Some(existing) => existing.1 = v.clone(),
+                        None => merged.push((key.clone(), v.clone())),
+                    }
+                }
+                Value::new(Node::Map(merged), value.pos)
+            }
+            (_, Some(base_name)) => return error(base_name, "only mappings can be extended"),
+            (Node::List(items), None) => {
+                Value::new(Node::List(self.expand_transforms(items)?), value.pos)
+            }
+            _ => value.clone(),
+        };
+
+        self.defines.insert(name, value);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &Value) -> Result<&Value, ParseError> {
+        let key = string(name)?;
+        self.defines
+            .get(key)
+            .map_or_else(|| error(name, format!("`{key}` hasn't been defined")), Ok)
+    }
+
+    /// Replaces references to defined transform lists with their contents
+    fn expand_transforms(&self, items: &[Value]) -> Result<Vec<Value>, ParseError> {
+        let mut expanded = Vec::new();
+        for item in items {
+            match &item.node {
+                Node::String(_) => {
+                    let define = self.lookup(item)?;
+                    let Node::List(inner) = &define.node else {
+                        return error(item, "expected the name of a transform list");
+                    };
+                    expanded.extend(inner.iter().cloned());
+                }
+                _ => expanded.push(item.clone()),
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn camera(&mut self, entry: &Value, fields: &[(String, Value)]) -> Result<(), ParseError> {
+        check_keys(
+            fields,
+            &[
+                "add",
+                "width",
+                "height",
+                "field-of-view",
+                "view",
+                "from",
+                "to",
+                "up",
+                "orientation",
+                "shutter",
+                "keyframes",
+            ],
+            "a camera",
+        )?;
+        let width = size(require(fields, "width", entry, "a camera")?)?;
+        let height = size(require(fields, "height", entry, "a camera")?)?;
+        let from = point(require(fields, "from", entry, "a camera")?)?;
+
+        let (view_width, view_height) = match (get(fields, "field-of-view"), get(fields, "view")) {
+            (Some(_), Some(view)) => {
+                return error(view, "a camera takes `field-of-view` or `view`, not both")
+            }
+            (Some(fov), None) => {
+                let half_view = (number(fov)? / 2.0).tan();
+                if half_view.is_nan() || half_view <= 0.0 {
+                    return error(fov, "field of view should be between 0 and pi");
+                }
+
+                let aspect = width as f32 / height as f32;
+                let (half_width, half_height) = if aspect >= 1.0 {
+                    (half_view, half_view / aspect)
+                } else {
+                    (half_view * aspect, half_view)
+                };
+                (half_width * 2.0, half_height * 2.0)
+            }
+            (None, Some(view)) => match &view.node {
+                Node::List(items) if items.len() == 2 => {
+                    let (w, h) = (number(&items[0])?, number(&items[1])?);
+                    if !(w > 0.0 && h > 0.0 && w.is_finite() && h.is_finite()) {
+                        return error(view, "the view's width and height should be above 0");
+                    }
+                    (w, h)
+                }
+                _ => return error(view, "expected the width and height of the view"),
+            },
+            (None, None) => return error(entry, "a camera is missing `field-of-view`"),
+        };
+        let camera = Viewport::new(from, view_width, view_height);
+
+        let (to, up, camera) = match get(fields, "orientation") {
+            Some(value) => {
+                if get(fields, "to").is_some() || get(fields, "up").is_some() {
+                    return error(
+                        value,
+                        "a camera takes `to` and `up` or `orientation`, not both",
+                    );
+                }
+                let values = match &value.node {
+                    Node::List(items) if items.len() == 16 => {
+                        items.iter().map(number).collect::<Result<Vec<_>, _>>()?
+                    }
+                    _ => return error(value, "expected the 16 numbers of a matrix"),
+                };
+                let orientation = Mat4::new(std::array::from_fn(|r| {
+                    std::array::from_fn(|c| values[(r * 4) + c])
+                }));
+                let camera = Viewport {
+                    orientation,
+                    ..camera
+                };
+                (None, None, camera)
+            }
+            None => {
+                let to = point(require(fields, "to", entry, "a camera")?)?;
+                let up = require(fields, "up", entry, "a camera")?;
+                let Some(camera) = camera.look_at(to, vector(up)?) else {
+                    return error(
+                        up,
+                        "`up` can't be parallel to the direction from `from` to `to`",
+                    );
+                };
+                (Some(to), Some(up), camera)
+            }
+        };
+
+        if let Some(value) = get(fields, "shutter") {
+            let (open, close) = match &value.node {
+                Node::List(items) if items.len() == 2 => (number(&items[0])?, number(&items[1])?),
+                _ => return error(value, "expected a list of 2 times"),
+            };
+            if close < open {
+                return error(value, "the shutter can't close before it opens");
+            }
+            self.file.shutter = (open, close);
+        }
+
+        if let Some(value) = get(fields, "keyframes") {
+            let (Some(to), Some(up)) = (to, up) else {
+                return error(
+                    value,
+                    "camera keyframes need `to` and `up`, not `orientation`",
+                );
+            };
+            let up = vector(up)?;
+            let keys = keyframes(value, &["from", "to"], "a camera keyframe", |item, f| {
+                let key = CameraKey {
+                    from: get(f, "from").map_or(Ok(from), point)?,
+                    to: get(f, "to").map_or(Ok(to), point)?,
+                };
+                if Viewport::new(key.from, 1.0, 1.0)
+                    .look_at(key.to, up)
+                    .is_none()
+                {
+                    return error(
+                        item,
+                        "`up` can't be parallel to the direction from `from` to `to`",
+                    );
+                }
+                Ok(key)
+            })?;
+            self.file.animation.camera = Some(CameraTrack { keys, up });
+        }
+
+        self.file.camera = camera;
+        self.file.width = width;
+        self.file.height = height;
+        Ok(())
+    }
+
+    fn light(&mut self, entry: &Value, fields: &[(String, Value)]) -> Result<(), ParseError> {
+        check_keys(fields, &["add", "at", "intensity", "keyframes"], "a light")?;
+        let at = point(require(fields, "at", entry, "a light")?)?;
+        let intensity = color(require(fields, "intensity", entry, "a light")?)?;
+
+        if let Some(value) = get(fields, "keyframes") {
+            let track = keyframes(value, &["at", "intensity"], "a light keyframe", |_, f| {
+                Ok(LightKey {
+                    at: get(f, "at").map_or(Ok(at), point)?,
+                    intensity: get(f, "intensity").map_or(Ok(intensity), color)?,
+                })
+            })?;
+            let index = self.file.scene.lights.len();
+            self.file.animation.lights.push((index, track));
+        }
+
+        self.file.scene.lights.push(PointLight::new(at, intensity));
+        Ok(())
+    }
+
+    fn background(&mut self, entry: &Value, fields: &[(String, Value)]) -> Result<(), ParseError> {
+        check_keys(fields, &["add", "color"], "a background")?;
+        let value = require(fields, "color", entry, "a background")?;
+
+        let mut bytes = [0; 3];
+        for (byte, c) in bytes.iter_mut().zip(triple(value)?) {
+            if !(0.0..=255.0).contains(&c) || c.fract() != 0.0 {
+                return error(
+                    value,
+                    "background channels should be whole numbers from 0 to 255",
+                );
+            }
+            *byte = c as u8;
+        }
+
+        self.file.scene.bg_color = bytes;
+        Ok(())
+    }
+
+    fn sphere(&mut self, fields: &[(String, Value)]) -> Result<(), ParseError> {
+        check_keys(
+            fields,
+            &["add", "material", "medium", "transform", "keyframes"],
+            "a sphere",
+        )?;
+
+        let material = match get(fields, "material") {
+            Some(value) => self.material(value)?,
+            None => Material::default().with_id(self.material_id(None)),
+        };
+        let medium = get(fields, "medium").map(medium).transpose()?;
+        let transform = match get(fields, "transform") {
+            Some(value) => self.transform(value)?,
+            None => identity_matrix!(),
+        };
+
+        let Some(sphere) = Sphere::try_new(transform, material) else {
+            let value = get(fields, "transform").expect("identity is always invertible");
+            return error(value, "transform can't be inverted");
+        };
+
+        if let Some(value) = get(fields, "keyframes") {
+            let poses = keyframes(
+                value,
+                &["translate", "rotate", "scale"],
+                "a sphere keyframe",
+                |_, f| pose(f),
+            )?;
+            let index = self.file.scene.spheres.len();
+            let track = TransformTrack::new(transform, poses).expect("checked above");
+            self.file.animation.spheres.push((index, track));
+        }
+
+        let sphere = match medium {
+            Some(medium) => sphere.with_medium(Arc::new(medium)),
+            None => sphere,
+        };
+        self.file.scene.spheres.push(Arc::new(sphere));
+        Ok(())
+    }
+
+    fn material(&mut self, value: &Value) -> Result<Material, ParseError> {
+        let name = match value.node {
+            Node::String(_) => Some(string(value)?),
+            _ => None,
+        };
+        let id = self.material_id(name);
+        let value = match name {
+            Some(_) => self.lookup(value)?,
+            None => value,
+        };
+        let fields = entries(value)?;
+        check_keys(
+            fields,
+            &["color", "ambient", "diffuse", "specular", "shininess"],
+            "a material",
+        )?;
+
+        let mut material = Material::default();
+        for (key, v) in fields {
+            match key.as_str() {
+                "color" => material.color = color(v)?,
+                "ambient" => material.ambient = number(v)?,
+                "diffuse" => material.diffuse = number(v)?,
+                "specular" => material.specular = number(v)?,
+                "shininess" => material.shine = number(v)?,
+                _ => unreachable!("keys were checked above"),
+            }
+        }
+        Ok(material.with_id(id))
+    }
+
+    /// The ID of the material defined as `name`, or a new one for materials given inline
+    fn material_id(&mut self, name: Option<&str>) -> usize {
+        let next = self.materials;
+        let id = match name {
+            Some(name) => *self.material_ids.entry(name.to_string()).or_insert(next),
+            None => next,
+        };
+        if id == next {
+            self.materials += 1;
+        }
+        id
+    }
+
+    fn transform(&self, value: &Value) -> Result<Mat4, ParseError> {
+        let items = match &value.node {
+            Node::List(items) => self.expand_transforms(items)?,
+            Node::String(_) => self.expand_transforms(std::slice::from_ref(value))?,
+            _ => return error(value, "expected a list of transforms"),
+        };
+
+        let mut matrix = identity_matrix!();
+        for item in &items {
+            let Node::List(parts) = &item.node else {
+                return error(item, "expected a transform like `[translate, 1, 2, 3]`");
+            };
+            let Some((op, args)) = parts.split_first() else {
+                return error(item, "expected a transform like `[translate, 1, 2, 3]`");
+            };
+
+            let args: Vec<f32> = args.iter().map(number).collect::<Result<_, _>>()?;
+            let expect = |n: usize| {
+                if args.len() == n {
+                    Ok(())
+                } else {
+                    error(item, format!("`{}` takes {n} numbers", string(op)?))
+                }
+            };
+
+            let step = match string(op)? {
+                "translate" => {
+                    expect(3)?;
+                    Mat4::translation(args[0], args[1], args[2])
+                }
+                "scale" => {
+                    expect(3)?;
+                    Mat4::scaling(args[0], args[1], args[2])
+                }
+                "rotate-x" => {
+                    expect(1)?;
+                    Mat4::rotation_x(args[0])
+                }
+                "rotate-y" => {
+                    expect(1)?;
+                    Mat4::rotation_y(args[0])
+                }
+                "rotate-z" => {
+                    expect(1)?;
+                    Mat4::rotation_z(args[0])
+                }
+                "shear" => {
+                    expect(6)?;
+                    Mat4::skew(args[0], args[1], args[2], args[3], args[4], args[5])
+                }
+                "matrix" => {
+                    expect(16)?;
+                    Mat4::new(std::array::from_fn(|r| {
+                        std::array::from_fn(|c| args[(r * 4) + c])
+                    }))
+                }
+                other => {
+                    return error(
+                        op,
+                        format!(
+                            "unknown transform `{other}`, expected translate, scale, rotate-x, \
+                             rotate-y, rotate-z, shear or matrix"
+                        ),
+                    )
+                }
+            };
+
+            matrix = step * matrix;
+        }
+
+        Ok(matrix)
+    }
+}
+
+#[cfg(test)]
+pub(crate) const EXAMPLE: &str = include_str!("../scenes/spheres.yml");
+
+#[test]
+pub fn test_scene_file_example() {
+    let file = SceneFile::parse(EXAMPLE).unwrap();
+
+    assert_eq!((file.width, file.height), (400, 200));
+    assert_eq!(file.scene.lights.len(), 1);
+    assert_eq!(file.scene.spheres.len(), 3);
+    assert_eq!(file.scene.bg_color, [20, 20, 30]);
+
+    // `extend` keeps the rest of the base material
+    let middle = &file.scene.spheres[1].material;
+    assert_eq!(middle.color, Color(1.0, 0.2, 1.0));
+    assert_eq!(middle.specular, 0.1);
+    assert_eq!(middle.shine, 200.0);
+    let ids: Vec<_> = file.scene.spheres.iter().map(|s| s.material.id).collect();
+    assert_eq!(ids, [Some(0), Some(1), Some(2)]);
+
+    // only spheres naming the same define share a material ID
+    let shared = SceneFile::parse(
+        "- define: red\n  value:\n    color: [1, 0, 0]\n\
+         - add: sphere\n  material: red\n\
+         - add: sphere\n  material:\n    color: [1, 0, 0]\n\
+         - add: sphere\n  material: red\n\
+         - add: sphere\n",
+    )
+    .unwrap();
+    let ids: Vec<_> = shared.scene.spheres.iter().map(|s| s.material.id).collect();
+    assert_eq!(ids, [Some(0), Some(1), Some(0), Some(2)]);
+
+    // scaled first, then moved by the `lift` define
+    let small = &file.scene.spheres[2];
+    assert_eq!(
+        small.transform() * Pos3::new(0.0, 0.0, 0.0),
+        Pos3::new(1.5, 1.0, -0.5)
+    );
+    assert_eq!(
+        small.transform() * Pos3::new(1.0, 0.0, 0.0),
+        Pos3::new(1.75, 1.0, -0.5)
+    );
+
+    let center = file.camera.ray_through(0.0, 0.0, file.width, file.height);
+    assert_eq!(center.origin, Pos3::new(0.0, 1.5, -5.0));
+    assert_eq!(
+        center.dir.to_normalized(),
+        Vec3::new(0.0, -0.5, 5.0).to_normalized()
+    );
+}
+
+#[test]
+pub fn test_scene_file_errors() {
+    use crate::yaml::Position;
+
+    let error = |source: &str| SceneFile::parse(source).unwrap_err();
+
+    let e = error("- add: cube");
+    assert_eq!(e.pos, Position { line: 1, column: 8 });
+    assert!(e.message.contains("can't add `cube`"), "{e}");
+
+    let e = error("- add: sphere\n  material:\n    colour: [1, 0, 0]");
+    assert_eq!(
+        e.pos,
+        Position {
+            line: 3,
+            column: 13
+        }
+    );
+    assert!(e.message.contains("unknown key `colour`"), "{e}");
+
+    let e = error("- add: sphere\n  transform:\n    - [translate, 1, 2]");
+    assert_eq!(e.pos.line, 3);
+    assert_eq!(e.message, "`translate` takes 3 numbers");
+
+    let e = error("- add: sphere\n  material: shiny");
+    assert_eq!(
+        e.to_string(),
+        "line 2, column 13: `shiny` hasn't been defined"
+    );
+
+    let e = error("- add: light\n  at: [0, 0, 0]");
+    assert_eq!(e.message, "a light is missing `intensity`");
+
+    let e = error("- add: camera\n  width: 1\n  height: 1\n  view: [1, 0]\n  from: [0, 0, 0]");
+    assert_eq!(e.message, "the view's width and height should be above 0");
+}
+
+#[test]
+pub fn test_scene_file_round_trip() {
+    let loaded = SceneFile::parse(EXAMPLE).unwrap();
+    let saved = loaded.to_yaml().unwrap();
+    let reloaded = SceneFile::parse(&saved).unwrap();
+
+    // saving again gives exactly the same text, so nothing was lost the first time
+    assert_eq!(reloaded.to_yaml().unwrap(), saved);
+
+    let (a, b) = (&loaded.scene, &reloaded.scene);
+    assert_eq!(a.bg_color, b.bg_color);
+    assert_eq!(a.lights.len(), b.lights.len());
+    for (a, b) in a.lights.iter().zip(&b.lights) {
+        assert_eq!(
+            a.position.iter().map(f32::to_bits).collect::<Vec<_>>(),
+            b.position.iter().map(f32::to_bits).collect::<Vec<_>>()
+        );
+        assert_eq!(a.intensity, b.intensity);
+    }
+
+    assert_eq!(a.spheres.len(), b.spheres.len());
+    for (a, b) in a.spheres.iter().zip(&b.spheres) {
+        assert_eq!(a.transform().0, b.transform().0);
+        let (m, n) = (&a.material, &b.material);
+        assert_eq!(
+            [m.color.0, m.color.1, m.color.2, m.ambient, m.diffuse, m.specular, m.shine],
+            [n.color.0, n.color.1, n.color.2, n.ambient, n.diffuse, n.specular, n.shine]
+        );
+        assert_eq!(m.id, n.id);
+    }
+
+    assert_eq!(
+        (loaded.width, loaded.height),
+        (reloaded.width, reloaded.height)
+    );
+    let bits = |c: &Viewport| {
+        let values = c.orientation.0.iter().flatten().copied();
+        let values = values.chain(c.position.iter()).chain([c.width, c.height]);
+        values.map(f32::to_bits).collect::<Vec<_>>()
+    };
+    assert_eq!(bits(&loaded.camera), bits(&reloaded.camera));
+
+    // a view that doesn't match the image's shape, and a tilted camera, come back unchanged
+    let mut file = loaded.clone();
+    file.camera = Viewport::new(Pos3::new(0.3, 1.7, -4.1), 0.9, 1.3)
+        .look_at(Pos3::new(0.2, 0.1, 0.0), Vec3::new(0.3, 1.0, 0.1))
+        .unwrap();
+    let reloaded = SceneFile::parse(&file.to_yaml().unwrap()).unwrap();
+    assert_eq!(bits(&file.camera), bits(&reloaded.camera));
+
+    // media stay invisible boundaries rather than turning into solid spheres
+    let smoke = Medium::homogeneous(Color(0.1, 0.2, 0.3), Color(0.5, 0.5, 0.5))
+        .with_density(Density::Noise {
+            scale: 2.5,
+            octaves: 4,
+        })
+        .with_anisotropy(-0.3);
+    let mut file = loaded.clone();
+    let sphere = Sphere::clone(&file.scene.spheres[0]).with_medium(Arc::new(smoke.clone()));
+    file.scene.spheres[0] = Arc::new(sphere);
+    let reloaded = SceneFile::parse(&file.to_yaml().unwrap()).unwrap();
+    assert_eq!(reloaded.scene.spheres[0].medium.as_deref(), Some(&smoke));
+    assert!(reloaded.scene.spheres[1].medium.is_none());
+
+    // spheres sharing a material still share its ID
+    let shared = SceneFile::parse(
+        "- define: m\n  value:\n    color: [1, 0, 0]\n\
+         - add: sphere\n  material: m\n\
+         - add: sphere\n\
+         - add: sphere\n  material: m\n",
+    )
+    .unwrap();
+    let saved = shared.to_yaml().unwrap();
+    let reloaded = SceneFile::parse(&saved).unwrap();
+    let ids = |f: &SceneFile| -> Vec<_> { f.scene.spheres.iter().map(|s| s.material.id).collect() };
+    assert_eq!(ids(&shared), [Some(0), Some(1), Some(0)]);
+    assert_eq!(ids(&reloaded), ids(&shared));
+    assert_eq!(reloaded.to_yaml().unwrap(), saved);
+
+    // anything else the format can't hold stops the save instead of being dropped
+    let mut file = loaded.clone();
+    let texture = crate::objects::texture::Texture::new(
+        Arc::new(crate::objects::texture::ImageTexture::from_pixels(
+            1,
+            1,
+            vec![Color::WHITE],
+        )),
+        crate::objects::uv::UvMap::default(),
+    );
+    let mut sphere = Sphere::clone(&file.scene.spheres[2]);
+    sphere.material = sphere.material.with_texture(texture);
+    file.scene.spheres[2] = Arc::new(sphere);
+    assert_eq!(
+        file.to_yaml().unwrap_err().to_string(),
+        "scene files can't describe the texture on sphere 2"
+    );
+}
+
+#[test]
+pub fn test_scene_file_keyframes() {
+    let file = SceneFile::parse(include_str!("../scenes/turntable.yml")).unwrap();
+    assert_eq!(file.animation.duration(), 3.0);
+
+    // loaded at time 0, which leaves the orbiting sphere where its transform puts it
+    let center = |f: &SceneFile| f.scene.spheres[1].transform() * Pos3::new(0.0, 0.0, 0.0);
+    assert_eq!(center(&file), Pos3::new(2.0, 0.0, 0.0));
+    assert_eq!(center(&file.at(0.75)), Pos3::new(0.0, 0.0, -2.0));
+    assert_eq!(center(&file.at(1.5)), Pos3::new(-2.0, 0.0, 0.0));
+    assert_eq!(center(&file.at(3.0)), Pos3::new(2.0, 0.0, 0.0));
+
+    let halfway = file.at(1.5);
+    assert_eq!(halfway.scene.lights[0].intensity, Color(0.7, 0.7, 0.75));
+    assert_eq!(file.at(3.0).camera.position, Pos3::new(0.0, 3.0, -8.0));
+    assert_eq!(
+        halfway.camera.forward(),
+        halfway.camera.forward().to_normalized()
+    );
+
+    // keys and the unanimated values they build on survive saving
+    let reloaded = SceneFile::parse(&file.to_yaml().unwrap()).unwrap();
+    let saved = reloaded.to_yaml().unwrap();
+    assert_eq!(saved, SceneFile::parse(&saved).unwrap().to_yaml().unwrap());
+    for time in [0.0, 0.4, 1.5, 2.9] {
+        let (a, b) = (file.at(time), reloaded.at(time));
+        assert_eq!(center(&a), center(&b));
+        assert_eq!(a.scene.lights[0].intensity, b.scene.lights[0].intensity);
+        assert_eq!(a.camera.position, b.camera.position);
+    }
+
+    // an open shutter follows each frame and makes moving spheres blur
+    let blurred = SceneFile {
+        shutter: (0.0, 0.02),
+        ..file.clone()
+    };
+    let frame = blurred.at(1.5);
+    assert_eq!(frame.camera.shutter, (1.5, 1.52));
+    assert!(frame.scene.spheres[1].motion.is_some());
+    assert!(file.at(1.5).scene.spheres[1].motion.is_none());
+    let reloaded = SceneFile::parse(&blurred.to_yaml().unwrap()).unwrap();
+    assert_eq!(reloaded.shutter, (0.0, 0.02));
+
+    let e = SceneFile::parse(
+        "- add: camera\n  width: 1\n  height: 1\n  field-of-view: 1\n  from: [0, 0, -1]\n  \
+         to: [0, 0, 0]\n  up: [0, 1, 0]\n  shutter: [0.5, 0]",
+    )
+    .unwrap_err();
+    assert_eq!(e.message, "the shutter can't close before it opens");
+
+    let e = SceneFile::parse(
+        "- add: light\n  at: [0, 0, 0]\n  intensity: [1, 1, 1]\n  \
+         keyframes:\n    keys:\n      - at: [1, 1, 1]",
+    )
+    .unwrap_err();
+    assert_eq!(e.message, "a light keyframe is missing `time`");
+
+    let e = SceneFile::parse(
+        "- add: sphere\n  keyframes:\n    interpolation: bouncy\n    keys:\n      - time: 0",
+    )
+    .unwrap_err();
+    assert!(
+        e.message.starts_with("unknown interpolation `bouncy`"),
+        "{e}"
+    );
+}
+
+#[test]
+pub fn test_scene_file_flip() {
+    use crate::{render::RenderSettings, Ray, Renderer};
+
+    // mirrored by the end, so halfway through the sphere is scaled flat
+    let file = SceneFile::parse(
+        "- add: camera\n  width: 4\n  height: 4\n  field-of-view: 1\n  from: [0, 0, -5]\n  \
+         to: [0, 0, 0]\n  up: [0, 1, 0]\n\
+         - add: sphere\n  keyframes:\n    keys:\n      - time: 0\n        scale: [1, 1, 1]\n      \
+         - time: 1\n        scale: [-1, 1, 1]\n",
+    )
+    .unwrap();
+
+    // frames 0..3 at 4 fps, where the third used to panic
+    for frame in 0..3 {
+        let posed = file.at(frame as f32 / 4.0);
+        let settings = RenderSettings {
+            width: 4,
+            height: 4,
+            ..Default::default()
+        };
+        Renderer::new(posed.scene, posed.camera, settings).render();
+    }
+
+    // and the flat frame keeps the pose the sphere was loaded with
+    let transform = |f: &SceneFile| *f.scene.spheres[0].transform();
+    assert_eq!(transform(&file.at(0.5)), transform(&file));
+
+    // with the shutter open across it, rays at that moment miss instead
+    let blurred = SceneFile {
+        shutter: (0.0, 0.1),
+        ..file.clone()
+    }
+    .at(0.45);
+    let ray = Ray::new(Pos3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    let hits = |time| {
+        ray.clone()
+            .with_time(time)
+            .sphere_intersect(&blurred.scene.spheres[0])
+    };
+    assert!(hits(0.5).is_empty());
+    assert_eq!(hits(0.45)[0].t, 4.0);
+}
@@ -0,0 +1,227 @@
+//! Saves a sequence of rendered frames as a single animated image, so turntables and other
+//! animations can be shared without putting them together with another tool
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use image::RgbImage;
+
+use crate::{
+    palette::{Dither, Palette},
+    Framebuffer,
+};
+
+/// The animated formats `save_sequence` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceFormat {
+    /// Up to 256 colors shared by every frame, see `Palette`
+    Gif,
+    /// Animated PNG, in full color. Viewers without APNG support show the first frame.
+    Apng,
+}
+
+impl SequenceFormat {
+    /// Picked from the extension, `.gif` or `.apng`. A plain `.png` is left to be a still image.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(Self::Gif),
+            "apng" => Some(Self::Apng),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceSettings {
+    pub fps: f32,
+    /// How many times the animation plays, or 0 to loop forever
+    pub loops: u16,
+    /// GIF only, the size of the palette
+    pub colors: usize,
+    /// GIF only, how pixels are snapped to the palette
+    pub dither: Dither,
+}
+
+impl Default for SequenceSettings {
+    fn default() -> Self {
+        Self {
+            fps: 24.0,
+            loops: 0,
+            colors: Palette::MAX_COLORS,
+            dither: Dither::None,
+        }
+    }
+}
+
+/// Saves `frames` in the format implied by the path's extension, see `SequenceFormat`. Like
+/// `Framebuffer::save`, colors outside of [0, 1] are clamped, so tone map first.
+pub fn save_sequence(
+    frames: &[Framebuffer],
+    path: impl AsRef<Path>,
+    settings: &SequenceSettings,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let Some(format) = SequenceFormat::from_path(path) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "animations can only be saved as .gif or .apng",
+        ));
+    };
+
+    let images: Vec<RgbImage> = frames.iter().map(|f| f.to_rgb_image()).collect();
+    let mut out = BufWriter::new(File::create(path)?);
+    match format {
+        SequenceFormat::Gif => write_gif(&images, &mut out, settings)?,
+        SequenceFormat::Apng => write_apng(&images, &mut out, settings)?,
+    }
+
+    out.flush()
+}
+
+/// Writes an animated GIF with one palette for every frame
+pub fn write_gif(
+    frames: &[RgbImage],
+    out: &mut impl Write,
+    settings: &SequenceSettings,
+) -> io::Result<()> {
+    let (width, height) = frame_size(frames)?;
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(invalid("GIF frames can't be more than 65535 pixels across"));
+    };
+
+    let palette = Palette::from_images(frames, settings.colors);
+    let colors: Vec<u8> = palette.colors().iter().flatten().copied().collect();
+
+    let mut encoder = gif::Encoder::new(out, width, height, &colors).map_err(io::Error::other)?;
+    let repeat = match settings.loops {
+        0 => gif::Repeat::Infinite,
+        n => gif::Repeat::Finite(n - 1),
+    };
+    encoder.set_repeat(repeat).map_err(io::Error::other)?;
+
+    for (image, delay) in frames.iter().zip(gif_delays(frames.len(), settings.fps)) {
+        let frame = gif::Frame {
+            delay,
+            width,
+            height,
+            buffer: palette.map(image, settings.dither).into(),
+            ..Default::default()
+        };
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+
+    Ok(())
+}
+
+/// Writes an animated PNG, keeping every color
+pub fn write_apng(
+    frames: &[RgbImage],
+    out: &mut impl Write,
+    settings: &SequenceSettings,
+) -> io::Result<()> {
+    let (width, height) = frame_size(frames)?;
+
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, settings.loops as u32)?;
+    // hundredths of a second per frame, as close as 16 bits allow
+    let fps = (settings.fps * 100.0).round().clamp(1.0, u16::MAX as f32);
+    encoder.set_frame_delay(100, fps as u16)?;
+
+    let mut writer = encoder.write_header()?;
+    for image in frames {
+        writer.write_image_data(image.as_raw())?;
+    }
+
+    Ok(writer.finish()?)
+}
+
+/// GIF delays are whole hundredths of a second. Rounding the time each frame starts instead of
+/// each delay keeps the animation in step with `fps` overall, like 24 fps alternating 4 and 5.
+fn gif_delays(count: usize, fps: f32) -> impl Iterator<Item = u16> {
+    let start = move |frame: usize| (frame as f32 * 100.0 / fps).round() as u32;
+    (0..count).map(move |i| (start(i + 1) - start(i)).min(u16::MAX as u32) as u16)
+}
+
+/// The size every frame shares
+fn frame_size(frames: &[RgbImage]) -> io::Result<(u32, u32)> {
+    let Some(first) = frames.first() else {
+        return Err(invalid("an animation needs at least one frame"));
+    };
+    if frames.iter().any(|f| f.dimensions() != first.dimensions()) {
+        return Err(invalid(
+            "every frame of an animation has to be the same size",
+        ));
+    }
+
+    Ok(first.dimensions())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[test]
+pub fn test_sequence_export() {
+    use crate::Color;
+    use image::{codecs::gif::GifDecoder, codecs::png::PngDecoder, AnimationDecoder};
+
+    // a bright square moving across a dark background
+    let frames: Vec<Framebuffer> = (0..5)
+        .map(|i| {
+            let mut frame = Framebuffer::from_pixels(16, 8, vec![Color(0.05, 0.05, 0.1); 128]);
+            for y in 2..6 {
+                for x in (i * 3)..(i * 3 + 3) {
+                    frame.set(x, y, Color(1.0, 0.8, 0.2));
+                }
+            }
+            frame
+        })
+        .collect();
+    let images: Vec<RgbImage> = frames.iter().map(|f| f.to_rgb_image()).collect();
+
+    let delays: Vec<u16> = gif_delays(24, 24.0).collect();
+    assert_eq!(delays.iter().map(|&d| d as u32).sum::<u32>(), 100);
+    assert!(delays.iter().all(|&d| d == 4 || d == 5));
+
+    let settings = SequenceSettings {
+        dither: Dither::FloydSteinberg,
+        ..Default::default()
+    };
+    let name = |ext: &str| format!("raytrace_test_sequence_{}.{ext}", std::process::id());
+    let path = std::env::temp_dir().join(name("gif"));
+    save_sequence(&frames, &path, &settings).unwrap();
+    let decoded = GifDecoder::new(File::open(&path).unwrap())
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(decoded.len(), 5);
+    // only two colors, which the palette holds exactly, so dithering has nothing to spread
+    for (frame, image) in decoded.iter().zip(&images) {
+        let rgb = image::DynamicImage::ImageRgba8(frame.buffer().clone()).into_rgb8();
+        assert_eq!(&rgb, image);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    let path = std::env::temp_dir().join(name("apng"));
+    save_sequence(&frames, &path, &SequenceSettings::default()).unwrap();
+    let decoder = PngDecoder::new(File::open(&path).unwrap()).unwrap();
+    assert!(decoder.is_apng());
+    let decoded = decoder.apng().into_frames().collect_frames().unwrap();
+    assert_eq!(decoded.len(), 5);
+    assert_eq!(decoded[1].delay().numer_denom_ms(), (125, 3));
+    let rgb = image::DynamicImage::ImageRgba8(decoded[4].buffer().clone()).into_rgb8();
+    assert_eq!(rgb, images[4]);
+    std::fs::remove_file(&path).unwrap();
+
+    let e = save_sequence(&frames, "clip.mp4", &SequenceSettings::default()).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    let e = write_gif(&[], &mut Vec::new(), &settings).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
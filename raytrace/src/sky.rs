@@ -0,0 +1,232 @@
+use std::f32::consts::PI;
+
+use crate::{Color, DirectionalLight, Vec3};
+
+/// Coefficients of the Perez sky luminance distribution
+#[derive(Debug, Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32,
+}
+
+impl Perez {
+    /// `theta` is the angle from the zenith, `gamma` the angle from the sun
+    fn eval(&self, theta: f32, gamma: f32) -> f32 {
+        // keep the horizon finite
+        let cos_theta = theta.cos().max(0.01);
+        let cos_gamma = gamma.cos();
+
+        (1.0 + (self.a * (self.b / cos_theta).exp()))
+            * (1.0 + (self.c * (self.d * gamma).exp()) + (self.e * cos_gamma * cos_gamma))
+    }
+}
+
+/// Preetham et al.'s analytic daylight model. Provides the sky color for rays that escape the
+/// scene and a sun that lights it as a directional light.
+///
+/// Radiance is computed in kcd/m^2 and scaled down by `intensity`, so the defaults land near
+/// the [0, 1] range that the rest of the renderer expects.
+#[derive(Debug, Clone)]
+pub struct Sky {
+    sun_dir: Vec3,
+    turbidity: f32,
+    /// Multiplier on the sky's radiance
+    pub intensity: f32,
+    /// Fraction of the horizon's color reflected back up by the ground, for rays below it
+    pub ground_albedo: f32,
+    theta_sun: f32,
+    zenith: (f32, f32, f32),
+    perez: [Perez; 3],
+    /// Multiplier on the sun's color when acting as a light
+    sun_intensity: f32,
+    /// Worked out once up front, since it's needed for every shading point
+    sun: Option<DirectionalLight>,
+}
+
+impl Sky {
+    /// `sun_dir` points towards the sun. `turbidity` describes haze, from ~2 for a very clear sky
+    /// to ~10 for a hazy one.
+    pub fn new(sun_dir: Vec3, turbidity: f32) -> Self {
+        let sun_dir = sun_dir.to_normalized();
+        let t = turbidity;
+        // the model is only defined for a sun above the horizon
+        let theta_sun = sun_dir.y.clamp(0.0, 1.0).acos().min(PI / 2.0 - 0.001);
+
+        let chi = ((4.0 / 9.0) - (t / 120.0)) * (PI - (2.0 * theta_sun));
+        let zenith_luminance = (((4.0453 * t) - 4.9710) * chi.tan()) - (0.2155 * t) + 2.4192;
+
+        let (t2, th, th2, th3) = (t * t, theta_sun, theta_sun.powi(2), theta_sun.powi(3));
+        let zenith_x = (t2 * ((0.00166 * th3) - (0.00375 * th2) + (0.00209 * th)))
+            + (t * ((-0.02903 * th3) + (0.06377 * th2) - (0.03202 * th) + 0.00394))
+            + ((0.11693 * th3) - (0.21196 * th2) + (0.06052 * th) + 0.25886);
+        let zenith_y = (t2 * ((0.00275 * th3) - (0.00610 * th2) + (0.00317 * th)))
+            + (t * ((-0.04214 * th3) + (0.08970 * th2) - (0.04153 * th) + 0.00516))
+            + ((0.15346 * th3) - (0.26756 * th2) + (0.06670 * th) + 0.26688);
+
+        let perez = [
+            Perez {
+                a: (0.1787 * t) - 1.4630,
+                b: (-0.3554 * t) + 0.4275,
+                c: (-0.0227 * t) + 5.3251,
+                d: (0.1206 * t) - 2.5771,
+                e: (-0.0670 * t) + 0.3703,
+            },
+            Perez {
+                a: (-0.0193 * t) - 0.2592,
+                b: (-0.0665 * t) + 0.0008,
+                c: (-0.0004 * t) + 0.2125,
+                d: (-0.0641 * t) - 0.8989,
+                e: (-0.0033 * t) + 0.0452,
+            },
+            Perez {
+                a: (-0.0167 * t) - 0.2608,
+                b: (-0.0950 * t) + 0.0092,
+                c: (-0.0079 * t) + 0.2102,
+                d: (-0.0441 * t) - 1.6537,
+                e: (-0.0109 * t) + 0.0529,
+            },
+        ];
+
+        let sky = Self {
+            sun_dir,
+            turbidity,
+            intensity: 0.05,
+            ground_albedo: 0.3,
+            theta_sun,
+            zenith: (zenith_luminance, zenith_x, zenith_y),
+            perez,
+            sun_intensity: 1.0,
+            sun: None,
+        };
+        Self {
+            sun: sky.sun_light(),
+            ..sky
+        }
+    }
+
+    /// Multiplier on the sun's color when acting as a light
+    pub fn with_sun_intensity(mut self, sun_intensity: f32) -> Self {
+        self.sun_intensity = sun_intensity;
+        self.sun = self.sun_light();
+        self
+    }
+
+    pub fn sun_dir(&self) -> Vec3 {
+        self.sun_dir
+    }
+
+    pub fn turbidity(&self) -> f32 {
+        self.turbidity
+    }
+
+    /// Returns the sky's color looking in direction `dir`. The sun's disc is left out, since it's
+    /// accounted for by `sun`.
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        let dir = dir.to_normalized();
+        let below = dir.y < 0.0;
+        // below the horizon, reflect the horizon's color off the ground
+        let dir = if below {
+            Vec3::new(dir.x, 0.0, dir.z).to_normalized()
+        } else {
+            dir
+        };
+
+        let theta = dir.y.clamp(-1.0, 1.0).acos();
+        let gamma = (dir * self.sun_dir).clamp(-1.0, 1.0).acos();
+
+        let relative =
+            |i: usize| self.perez[i].eval(theta, gamma) / self.perez[i].eval(0.0, self.theta_sun);
+
+        let luminance = self.zenith.0 * relative(0);
+        let x = self.zenith.1 * relative(1);
+        let y = self.zenith.2 * relative(2);
+
+        let color = xyy_to_rgb(x, y, luminance) * self.intensity;
+
+        if below {
+            color * self.ground_albedo
+        } else {
+            color
+        }
+    }
+
+    /// The sun as a light, dimmed and reddened by the atmosphere it passes through. There's none
+    /// once it has set.
+    pub fn sun(&self) -> Option<DirectionalLight> {
+        self.sun
+    }
+
+    fn sun_light(&self) -> Option<DirectionalLight> {
+        if self.sun_dir.y < 0.0 {
+            return None;
+        }
+
+        // relative optical air mass, from Kasten and Young
+        let elevation = 90.0 - self.theta_sun.to_degrees();
+        let air_mass =
+            1.0 / (self.theta_sun.cos() + (0.50572 * (elevation + 6.07995).powf(-1.6364)));
+
+        // Angstrom's turbidity formula for aerosols, plus Rayleigh scattering
+        let alpha = 1.3;
+        let beta = (0.04608 * self.turbidity) - 0.04586;
+        let transmittance = |lambda_um: f32| {
+            let rayleigh = (-0.008735 * lambda_um.powf(-4.08) * air_mass).exp();
+            let aerosol = (-beta * lambda_um.powf(-alpha) * air_mass).exp();
+            rayleigh * aerosol
+        };
+
+        Some(DirectionalLight::new(
+            self.sun_dir,
+            Color(
+                transmittance(0.680),
+                transmittance(0.550),
+                transmittance(0.440),
+            ) * self.sun_intensity,
+        ))
+    }
+}
+
+/// Converts CIE xyY chromaticity and luminance into linear sRGB
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0.0 {
+        return Color::BLACK;
+    }
+
+    let cx = x / y * luminance;
+    let cy = luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+
+    Color(
+        ((3.2406 * cx) - (1.5372 * cy) - (0.4986 * cz)).max(0.0),
+        ((-0.9689 * cx) + (1.8758 * cy) + (0.0415 * cz)).max(0.0),
+        ((0.0557 * cx) - (0.2040 * cy) + (1.0570 * cz)).max(0.0),
+    )
+}
+
+#[test]
+pub fn test_sky_zenith_is_blue() {
+    let sky = Sky::new(Vec3::new(0.0, 1.0, 1.0), 3.0);
+    let zenith = sky.radiance(Vec3::new(0.0, 1.0, 0.0));
+
+    assert!(zenith.2 > zenith.0, "{zenith:?}");
+
+    // brighter around the sun than opposite it
+    let towards = sky.radiance(Vec3::new(0.0, 0.5, 1.0));
+    let away = sky.radiance(Vec3::new(0.0, 0.5, -1.0));
+    assert!(towards.luminance() > away.luminance());
+}
+
+#[test]
+pub fn test_sky_sunset_is_red() {
+    let noon = Sky::new(Vec3::new(0.0, 1.0, 0.2), 3.0).sun().unwrap();
+    let sunset = Sky::new(Vec3::new(0.0, 0.05, 1.0), 3.0).sun().unwrap();
+
+    assert!(noon.intensity.luminance() > sunset.intensity.luminance());
+    assert!(sunset.intensity.0 / sunset.intensity.2 > noon.intensity.0 / noon.intensity.2);
+
+    // once it's below the horizon, it stops lighting the scene
+    assert!(Sky::new(Vec3::new(0.0, -0.1, 1.0), 3.0).sun().is_none());
+}
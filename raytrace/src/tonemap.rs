@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use image::ImageResult;
+
+use crate::{export, Color, Framebuffer};
+
+/// Operators for compressing unbounded scene colors into the [0, 1] display range. Defaults to
+/// `Aces`, like the binary's `--tonemap`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMap {
+    /// Leaves colors alone, so anything above 1.0 clips
+    Clamp,
+    /// `c / (1 + c)`, which never quite reaches white
+    Reinhard,
+    /// Reinhard, rescaled so that `white` and above map to 1.0
+    ReinhardExtended { white: f32 },
+    /// Narkowicz's fit of the ACES filmic curve
+    #[default]
+    Aces,
+}
+
+impl ToneMap {
+    /// The operators that can be picked by name, see `from_name`
+    pub const NAMED: [ToneMap; 3] = [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToneMap::Clamp => "none",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::ReinhardExtended { .. } => "reinhard-extended",
+            ToneMap::Aces => "aces",
+        }
+    }
+
+    /// One of `NAMED`. `ReinhardExtended` needs a white point, so it can't be picked by name.
+    pub fn from_name(name: &str) -> Option<ToneMap> {
+        ToneMap::NAMED.into_iter().find(|op| op.name() == name)
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        let curve = |c: f32| -> f32 {
+            let c = c.max(0.0);
+            match self {
+                ToneMap::Clamp => c,
+                ToneMap::Reinhard => c / (1.0 + c),
+                ToneMap::ReinhardExtended { white } => {
+                    (c * (1.0 + (c / (white * white)))) / (1.0 + c)
+                }
+                ToneMap::Aces => {
+                    (c * ((2.51 * c) + 0.03)) / ((c * ((2.43 * c) + 0.59)) + 0.14)
+                }
+            }
+            .min(1.0)
+        };
+
+        Color(curve(color.0), curve(color.1), curve(color.2))
+    }
+}
+
+/// Exposure and tone mapping applied when turning a rendered `Framebuffer` into a displayable one
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ToneMapper {
+    pub operator: ToneMap,
+    /// In stops, so each +1.0 doubles the brightness before tone mapping
+    pub exposure: f32,
+}
+
+impl ToneMapper {
+    pub fn new(operator: ToneMap, exposure: f32) -> Self {
+        Self { operator, exposure }
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        self.operator.apply(color * self.exposure.exp2())
+    }
+}
+
+impl Framebuffer {
+    /// Returns a copy with every pixel in display range. The result is still linear, sRGB encoding
+    /// happens when converting to bytes.
+    pub fn tone_mapped(&self, mapper: &ToneMapper) -> Framebuffer {
+        Framebuffer::from_pixels(
+            self.width(),
+            self.height(),
+            self.pixels().iter().map(|p| mapper.apply(*p)).collect(),
+        )
+    }
+
+    /// Like `save`, tone mapping first unless the format keeps the full dynamic range
+    pub fn save_tone_mapped(&self, path: impl AsRef<Path>, mapper: &ToneMapper) -> ImageResult<()> {
+        let path = path.as_ref();
+        if export::is_high_dynamic_range(path) {
+            self.save(path)
+        } else {
+            self.tone_mapped(mapper).save(path)
+        }
+    }
+}
+
+#[test]
+pub fn test_tonemap_highlights() {
+    // the brightest result from `test_lighting_behindcam`
+    let bright = Color(1.9, 1.9, 1.9);
+    let dim = Color(0.5, 0.5, 0.5);
+
+    assert_eq!(ToneMap::Clamp.apply(bright), Color::WHITE);
+    assert_eq!(ToneMap::Reinhard.apply(dim), Color(1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0));
+    assert_eq!(
+        ToneMap::ReinhardExtended { white: 1.9 }.apply(bright),
+        Color::WHITE
+    );
+
+    for op in [
+        ToneMap::Reinhard,
+        ToneMap::ReinhardExtended { white: 4.0 },
+        ToneMap::Aces,
+    ] {
+        let hi = op.apply(bright);
+        let lo = op.apply(dim);
+        assert!(hi.0 < 1.0 && lo.0 < hi.0, "{op:?}");
+    }
+
+    // one stop up doubles the input
+    let mapper = ToneMapper::new(ToneMap::Reinhard, 1.0);
+    assert_eq!(mapper.apply(dim), Color(0.5, 0.5, 0.5));
+
+    assert_eq!(ToneMapper::default(), ToneMapper::new(ToneMap::Aces, 0.0));
+    assert_eq!(ToneMap::from_name("aces"), Some(ToneMap::Aces));
+    assert_eq!(ToneMap::from_name("none"), Some(ToneMap::Clamp));
+    assert_eq!(ToneMap::from_name("reinhard-extended"), None);
+}
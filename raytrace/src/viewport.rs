@@ -1,12 +1,17 @@
-use std::sync::Arc;
-
-use crate::{objects::{Sphere, material::Material}, scene::Intersection, Matrix, Object, Pos3, Vec3, Ray};
+use crate::{identity_matrix, Mat4, Pos3, Ray, Vec3};
 
+/// A camera looking through a `width` by `height` window one unit in front of it. Without any
+/// orientation it looks along +Z with +Y up.
 #[derive(Debug, Clone)]
 pub struct Viewport {
     pub position: Pos3,
     pub width: f32,
     pub height: f32,
+    /// Rotates camera-space directions into world space
+    pub orientation: Mat4,
+    /// When the shutter opens and closes, in seconds of animation time. Samples are spread over
+    /// the whole interval, blurring anything that moves while it's open.
+    pub shutter: (f32, f32),
 }
 
 impl Default for Viewport {
@@ -19,6 +24,8 @@ impl Default for Viewport {
             },
             width: 1.0,
             height: 1.0,
+            orientation: identity_matrix!(),
+            shutter: (0.0, 0.0),
         }
     }
 }
@@ -29,9 +36,52 @@ impl Viewport {
             position,
             width,
             height,
+            orientation: identity_matrix!(),
+            shutter: (0.0, 0.0),
         }
     }
 
+    pub fn with_shutter(mut self, open: f32, close: f32) -> Self {
+        self.shutter = (open, close);
+        self
+    }
+
+    /// Whether the shutter stays open long enough for anything to blur
+    pub fn has_motion_blur(&self) -> bool {
+        self.shutter.1 > self.shutter.0
+    }
+
+    /// Turns the camera to face `target`, with `up` pointing roughly towards the top of the
+    /// image. Returns `None` if `up` is parallel to the view direction.
+    pub fn look_at(mut self, target: Pos3, up: Vec3) -> Option<Self> {
+        let forward = (target - self.position).to_normalized();
+        let right = up.cross_product(forward);
+        if right.magnitude() < 1e-6 || !right.magnitude().is_finite() {
+            return None;
+        }
+
+        let right = right.to_normalized();
+        let up = forward.cross_product(right);
+
+        self.orientation = Mat4::new([
+            [right.x, up.x, forward.x, 0.0],
+            [right.y, up.y, forward.y, 0.0],
+            [right.z, up.z, forward.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Some(self)
+    }
+
+    /// The world-space direction the camera faces
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::new(0.0, 0.0, 1.0)
+    }
+
+    /// The world-space direction towards the top of the image
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::new(0.0, 1.0, 0.0)
+    }
+
     pub fn ray_from_coord(
         &self,
         x: isize,
@@ -39,9 +89,42 @@ impl Viewport {
         canvas_width: usize,
         canvas_height: usize,
     ) -> Ray {
-        let x = x as f32 * (self.width / (canvas_width as f32));
-        let y = y as f32 * (self.height / (canvas_height as f32));
+        self.ray_through(x as f32, y as f32, canvas_width, canvas_height)
+    }
+
+    /// Like `ray_from_coord`, but for any point on the canvas rather than just whole pixels
+    pub fn ray_through(&self, x: f32, y: f32, canvas_width: usize, canvas_height: usize) -> Ray {
+        let x = x * (self.width / (canvas_width as f32));
+        let y = y * (self.height / (canvas_height as f32));
 
-        Ray::new(self.position, Vec3::new(x, y, 1.0))
+        Ray::new(self.position, self.orientation * Vec3::new(x, y, 1.0)).with_time(self.shutter.0)
     }
 }
+
+#[test]
+pub fn test_viewport_look_at() {
+    let camera = Viewport::new(Pos3::new(0.0, 0.0, -5.0), 1.0, 1.0);
+    let ray = camera.ray_through(10.0, 0.0, 20, 20);
+
+    // looking the way it already faces changes nothing
+    let turned = camera
+        .clone()
+        .look_at(Pos3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+        .unwrap();
+    assert_eq!(turned.ray_through(10.0, 0.0, 20, 20), ray);
+
+    // facing +X, the right edge of the image is towards -Z
+    let turned = camera
+        .clone()
+        .look_at(Pos3::new(1.0, 0.0, -5.0), Vec3::new(0.0, 1.0, 0.0))
+        .unwrap();
+    assert_eq!(turned.forward(), Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(
+        turned.ray_through(10.0, 0.0, 20, 20).dir,
+        Vec3::new(1.0, 0.0, -0.5)
+    );
+
+    assert!(camera
+        .look_at(Pos3::new(0.0, 5.0, -5.0), Vec3::new(0.0, 1.0, 0.0))
+        .is_none());
+}
@@ -0,0 +1,105 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::scene_file::{SceneError, SceneFile};
+
+/// What changed since the last call to `SceneWatcher::poll`
+#[derive(Debug)]
+pub enum Reload {
+    /// The file changed and loaded cleanly. It's now `SceneWatcher::scene`.
+    Loaded,
+    /// The file changed but couldn't be loaded. `SceneWatcher::scene` still holds the last
+    /// scene that did.
+    Failed(SceneError),
+}
+
+/// Keeps track of a scene file on disk, reloading it whenever it changes. Changes are noticed by
+/// polling the modification time and size, so no platform file-notification support is needed.
+#[derive(Debug)]
+pub struct SceneWatcher {
+    path: PathBuf,
+    /// Modification time and size as of the last poll, `None` while the file is missing
+    stamp: Option<(SystemTime, u64)>,
+    polled: bool,
+    scene: Option<SceneFile>,
+}
+
+impl SceneWatcher {
+    /// Doesn't touch the file until the first `poll`
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            stamp: None,
+            polled: false,
+            scene: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The most recent version of the file that loaded without errors
+    pub fn scene(&self) -> Option<&SceneFile> {
+        self.scene.as_ref()
+    }
+
+    /// Reloads the file if it changed since the last poll. The first poll always loads it.
+    pub fn poll(&mut self) -> Option<Reload> {
+        let stamp = fs::metadata(&self.path)
+            .and_then(|meta| Ok((meta.modified()?, meta.len())))
+            .ok();
+
+        // a missing file is reported once, like any other change
+        if self.polled && stamp == self.stamp {
+            return None;
+        }
+        self.stamp = stamp;
+        self.polled = true;
+
+        match SceneFile::open(&self.path) {
+            Ok(scene) => {
+                self.scene = Some(scene);
+                Some(Reload::Loaded)
+            }
+            Err(e) => Some(Reload::Failed(e)),
+        }
+    }
+}
+
+#[test]
+pub fn test_scene_watcher() {
+    let name = format!("raytrace_test_watch_{}.yml", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    let _ = fs::remove_file(&path);
+    // every version has a different length, so changes are seen even if the modification time
+    // doesn't move on between writes
+    let write = |source: &str| fs::write(&path, source).unwrap();
+
+    let mut watcher = SceneWatcher::new(&path);
+    assert!(matches!(
+        watcher.poll(),
+        Some(Reload::Failed(SceneError::Io(_)))
+    ));
+    assert!(watcher.poll().is_none());
+
+    write(crate::scene_file::EXAMPLE);
+    assert!(matches!(watcher.poll(), Some(Reload::Loaded)));
+    let spheres = watcher.scene().unwrap().scene.spheres.len();
+    assert!(watcher.poll().is_none());
+
+    // a broken edit keeps the last good scene around
+    write("- add: sphere\n  material: [\n");
+    assert!(matches!(watcher.poll(), Some(Reload::Failed(_))));
+    assert_eq!(watcher.scene().unwrap().scene.spheres.len(), spheres);
+    assert!(watcher.poll().is_none());
+
+    write("- add: sphere\n");
+    assert!(matches!(watcher.poll(), Some(Reload::Loaded)));
+    assert_eq!(watcher.scene().unwrap().scene.spheres.len(), 1);
+
+    fs::remove_file(&path).unwrap();
+}
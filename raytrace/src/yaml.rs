@@ -0,0 +1,509 @@
+//! Just enough YAML for scene files: block mappings and sequences nested by indentation, flow
+//! `[lists]` and `{maps}`, plain and quoted scalars, and `#` comments. Anchors, multi-line strings
+//! and multiple documents aren't supported.
+//!
+//! Every value remembers where it came from, so errors further down the line can point at it.
+
+use std::fmt;
+
+/// A 1-based line and column in the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Number(f32),
+    String(String),
+    List(Vec<Value>),
+    /// Keys keep the order they were written in
+    Map(Vec<(String, Value)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+    pub node: Node,
+    pub pos: Position,
+}
+
+impl Value {
+    pub fn new(node: Node, pos: Position) -> Self {
+        Self { node, pos }
+    }
+
+    /// Describes the kind of value for error messages
+    pub fn kind(&self) -> &'static str {
+        match self.node {
+            Node::Number(_) => "a number",
+            Node::String(_) => "a string",
+            Node::List(_) => "a list",
+            Node::Map(_) => "a mapping",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub pos: Position,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(pos: Position, message: impl Into<String>) -> Self {
+        Self {
+            pos,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pos, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A non-blank line with its comment removed
+#[derive(Debug, Clone)]
+struct Line<'a> {
+    number: usize,
+    /// Column where `text` starts, counting from 0. Lines starting with `- ` are rewritten to
+    /// start after the dash, so this is also the nesting depth.
+    indent: usize,
+    text: &'a str,
+}
+
+impl Line<'_> {
+    fn pos(&self) -> Position {
+        self.pos_at(0)
+    }
+
+    fn pos_at(&self, offset: usize) -> Position {
+        Position {
+            line: self.number,
+            column: self.indent + offset + 1,
+        }
+    }
+
+    fn is_list_item(&self) -> bool {
+        self.text == "-" || self.text.starts_with("- ")
+    }
+}
+
+/// Cuts off a trailing `# comment`, as long as the `#` isn't inside quotes
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return &text[..i],
+            None => {}
+        }
+        prev = c;
+    }
+    text
+}
+
+/// Finds the `:` separating a block mapping key from its value
+fn split_key(text: &str) -> Option<(&str, &str, usize)> {
+    if text.starts_with(['[', '{', '"', '\'']) {
+        return None;
+    }
+
+    let bytes = text.as_bytes();
+    let colon = (0..bytes.len())
+        .find(|&i| bytes[i] == b':' && (i + 1 == bytes.len() || bytes[i + 1] == b' '))?;
+
+    let key = text[..colon].trim_end();
+    if key.is_empty() {
+        return None;
+    }
+
+    let rest = &text[colon + 1..];
+    let skipped = rest.len() - rest.trim_start().len();
+    Some((key, rest.trim(), colon + 1 + skipped))
+}
+
+pub fn parse(source: &str) -> Result<Value, ParseError> {
+    let mut lines = Vec::new();
+    for (i, raw) in source.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        let content = text.trim_start_matches(' ');
+        if content.is_empty() {
+            continue;
+        }
+
+        let indent = text.len() - content.len();
+        let line = Line {
+            number: i + 1,
+            indent,
+            text: content,
+        };
+        if content.starts_with('\t') {
+            return Err(ParseError::new(
+                line.pos(),
+                "tabs can't be used for indentation",
+            ));
+        }
+        lines.push(line);
+    }
+
+    let Some(indent) = lines.first().map(|l| l.indent) else {
+        return Ok(Value::new(
+            Node::List(Vec::new()),
+            Position { line: 1, column: 1 },
+        ));
+    };
+
+    let mut parser = Parser { lines, index: 0 };
+    let value = parser.block(indent)?;
+
+    if let Some(line) = parser.lines.get(parser.index) {
+        return Err(ParseError::new(line.pos(), "unexpected indentation"));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn current(&self) -> Option<&Line<'a>> {
+        self.lines.get(self.index)
+    }
+
+    /// Parses whatever starts at the current line, which must be indented by `indent`
+    fn block(&mut self, indent: usize) -> Result<Value, ParseError> {
+        let line = self.lines[self.index].clone();
+        debug_assert_eq!(line.indent, indent);
+
+        if line.is_list_item() {
+            self.list(indent)
+        } else if split_key(line.text).is_some() {
+            self.map(indent)
+        } else {
+            self.index += 1;
+            flow(line.text, line.pos())
+        }
+    }
+
+    fn list(&mut self, indent: usize) -> Result<Value, ParseError> {
+        let pos = self.lines[self.index].pos();
+        let mut items = Vec::new();
+
+        while let Some(line) = self.current() {
+            if line.indent != indent || !line.is_list_item() {
+                break;
+            }
+
+            let rest = &line.text[1..];
+            let content = rest.trim_start();
+            if content.is_empty() {
+                // the item is a nested block on the following lines
+                let dash = line.pos();
+                self.index += 1;
+                match self.current() {
+                    Some(next) if next.indent > indent => {
+                        let next_indent = next.indent;
+                        items.push(self.block(next_indent)?);
+                    }
+                    _ => return Err(ParseError::new(dash, "list item has no value")),
+                }
+            } else {
+                // treat the rest of the line as if it started a block of its own, so that
+                // `- key: value` lines continue as a mapping on the lines below
+                let offset = 1 + (rest.len() - content.len());
+                let line = &mut self.lines[self.index];
+                line.indent += offset;
+                line.text = content;
+
+                let item_indent = line.indent;
+                items.push(self.block(item_indent)?);
+            }
+        }
+
+        Ok(Value::new(Node::List(items), pos))
+    }
+
+    fn map(&mut self, indent: usize) -> Result<Value, ParseError> {
+        let pos = self.lines[self.index].pos();
+        let mut entries: Vec<(String, Value)> = Vec::new();
+
+        while let Some(line) = self.current().cloned() {
+            if line.indent != indent || line.is_list_item() {
+                break;
+            }
+
+            let Some((key, rest, offset)) = split_key(line.text) else {
+                return Err(ParseError::new(line.pos(), "expected `key: value`"));
+            };
+            if entries.iter().any(|(k, _)| k == key) {
+                return Err(ParseError::new(
+                    line.pos(),
+                    format!("`{key}` is set more than once"),
+                ));
+            }
+
+            self.index += 1;
+            let value = if !rest.is_empty() {
+                flow(rest, line.pos_at(offset))?
+            } else {
+                match self.current() {
+                    Some(next) if next.indent > indent => {
+                        let next_indent = next.indent;
+                        self.block(next_indent)?
+                    }
+                    // sequences are allowed at the same indentation as their key
+                    Some(next) if next.indent == indent && next.is_list_item() => {
+                        self.list(indent)?
+                    }
+                    _ => return Err(ParseError::new(line.pos(), format!("`{key}` has no value"))),
+                }
+            };
+
+            if let Some(next) = self.current() {
+                if next.indent > indent {
+                    return Err(ParseError::new(next.pos(), "unexpected indentation"));
+                }
+            }
+
+            entries.push((key.to_string(), value));
+        }
+
+        Ok(Value::new(Node::Map(entries), pos))
+    }
+}
+
+/// Parses a value written on a single line, starting at `pos`
+fn flow(text: &str, pos: Position) -> Result<Value, ParseError> {
+    let mut flow = Flow {
+        chars: text.chars().collect(),
+        index: 0,
+        pos,
+    };
+
+    let value = flow.value(false)?;
+    flow.skip_whitespace();
+    if flow.index < flow.chars.len() {
+        return Err(ParseError::new(
+            flow.pos(),
+            "unexpected characters after value",
+        ));
+    }
+
+    Ok(value)
+}
+
+struct Flow {
+    chars: Vec<char>,
+    index: usize,
+    pos: Position,
+}
+
+impl Flow {
+    fn pos(&self) -> Position {
+        Position {
+            line: self.pos.line,
+            column: self.pos.column + self.index,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.index += 1;
+        }
+    }
+
+    /// `nested` is true inside brackets, where commas and closing brackets end plain scalars
+    fn value(&mut self, nested: bool) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        let pos = self.pos();
+
+        match self.peek() {
+            Some('[') => {
+                self.index += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']') {
+                        self.index += 1;
+                        break;
+                    }
+
+                    items.push(self.value(true)?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.index += 1,
+                        Some(']') => {}
+                        _ => return Err(ParseError::new(self.pos(), "expected `,` or `]`")),
+                    }
+                }
+                Ok(Value::new(Node::List(items), pos))
+            }
+            Some('{') => {
+                self.index += 1;
+                let mut entries: Vec<(String, Value)> = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some('}') {
+                        self.index += 1;
+                        break;
+                    }
+
+                    let key_pos = self.pos();
+                    let start = self.index;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c != ':' && c != '}' && c != ',')
+                    {
+                        self.index += 1;
+                    }
+                    let key: String = self.chars[start..self.index].iter().collect();
+                    let key = key.trim().to_string();
+                    if key.is_empty() || self.peek() != Some(':') {
+                        return Err(ParseError::new(key_pos, "expected `key: value`"));
+                    }
+                    if entries.iter().any(|(k, _)| *k == key) {
+                        return Err(ParseError::new(
+                            key_pos,
+                            format!("`{key}` is set more than once"),
+                        ));
+                    }
+                    self.index += 1;
+
+                    entries.push((key, self.value(true)?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.index += 1,
+                        Some('}') => {}
+                        _ => return Err(ParseError::new(self.pos(), "expected `,` or `}`")),
+                    }
+                }
+                Ok(Value::new(Node::Map(entries), pos))
+            }
+            Some(q @ ('"' | '\'')) => {
+                self.index += 1;
+                let start = self.index;
+                while self.peek().is_some_and(|c| c != q) {
+                    self.index += 1;
+                }
+                if self.peek().is_none() {
+                    return Err(ParseError::new(pos, "unterminated string"));
+                }
+
+                let text = self.chars[start..self.index].iter().collect();
+                self.index += 1;
+                Ok(Value::new(Node::String(text), pos))
+            }
+            _ => {
+                let start = self.index;
+                while let Some(c) = self.peek() {
+                    if nested && matches!(c, ',' | ']' | '}') {
+                        break;
+                    }
+                    self.index += 1;
+                }
+
+                let text: String = self.chars[start..self.index].iter().collect();
+                let text = text.trim();
+                if text.is_empty() {
+                    return Err(ParseError::new(pos, "expected a value"));
+                }
+
+                Ok(match text.parse::<f32>() {
+                    Ok(n) => Value::new(Node::Number(n), pos),
+                    Err(_) => Value::new(Node::String(text.to_string()), pos),
+                })
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_yaml_parse() {
+    let source = "\
+# a comment
+- add: camera
+  from: [0, 1.5, -5] # trailing comment
+  nested:
+    list:
+    - [scale, 0.5, 1e-1]
+    - name
+  flow: { a: 1, b: 'x # y' }
+-
+  define: 2
+";
+    let value = parse(source).unwrap();
+    let Node::List(items) = &value.node else {
+        panic!("expected a list, got {value:?}");
+    };
+    assert_eq!(items.len(), 2);
+
+    let Node::Map(entries) = &items[0].node else {
+        panic!("expected a map, got {:?}", items[0]);
+    };
+    let keys: Vec<&str> = entries.iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(keys, ["add", "from", "nested", "flow"]);
+    assert_eq!(entries[0].1.node, Node::String("camera".into()));
+    assert_eq!(entries[1].1.pos, Position { line: 3, column: 9 });
+
+    let Node::Map(nested) = &entries[2].1.node else {
+        panic!("expected a map");
+    };
+    let Node::List(list) = &nested[0].1.node else {
+        panic!("expected a list");
+    };
+    let Node::List(scale) = &list[0].node else {
+        panic!("expected a list");
+    };
+    assert_eq!(scale[2].node, Node::Number(0.1));
+    assert_eq!(
+        scale[2].pos,
+        Position {
+            line: 6,
+            column: 20
+        }
+    );
+    assert_eq!(list[1].node, Node::String("name".into()));
+
+    let Node::Map(flow) = &entries[3].1.node else {
+        panic!("expected a map");
+    };
+    assert_eq!(flow[1].1.node, Node::String("x # y".into()));
+
+    let Node::Map(second) = &items[1].node else {
+        panic!("expected a map");
+    };
+    assert_eq!(second[0].1.node, Node::Number(2.0));
+}
+
+#[test]
+pub fn test_yaml_errors() {
+    let error = |source: &str| parse(source).unwrap_err();
+
+    assert_eq!(error("a: [1, 2").pos, Position { line: 1, column: 9 });
+    assert_eq!(error("a: 1\n  b: 2").pos, Position { line: 2, column: 3 });
+    assert_eq!(error("a: 1\na: 2").message, "`a` is set more than once");
+    assert_eq!(error("- a: 1\n-").pos, Position { line: 2, column: 1 });
+    assert_eq!(error("a:").message, "`a` has no value");
+}
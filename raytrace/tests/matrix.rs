@@ -98,6 +98,21 @@ pub fn test_matrix_transpose() {
     let matrix_2 = Matrix::from_vec(data_2);
 
     assert_eq!(matrix_1.transposed(), matrix_2);
+
+    // rows become columns, so the shape flips too
+    let wide = Matrix::from_vec(vec![
+        vec![1.0, 2.0, 3.0],
+        vec![4.0, 5.0, 6.0],
+    ]);
+
+    let tall = Matrix::from_vec(vec![
+        vec![1.0, 4.0],
+        vec![2.0, 5.0],
+        vec![3.0, 6.0],
+    ]);
+
+    assert_eq!(wide.transposed(), tall);
+    assert_eq!(tall.transposed(), wide);
 }
 
 #[test]